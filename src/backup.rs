@@ -0,0 +1,216 @@
+//! Timestamped copies of config files, made before saving over them
+
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::source::is_glob;
+use crate::{ConfigFs, HyprlandConfig, RealFs};
+
+/// Copy of a config file made before saving over it, such as
+/// `hyprland.conf.bak.2026-10-18T10:00:00`
+///
+/// Saving makes backups when [`HyprlandConfig::keep_backups`] isn't 0.
+///
+/// ```rust
+/// use hyprparser::{Backup, HyprlandConfig, MemoryFs};
+///
+/// let fs = MemoryFs::new().with_file("/hypr/hyprland.conf", "$mod = SUPER\n");
+///
+/// let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+/// config.keep_backups = 5;
+/// config.set_variable("mod", "ALT");
+/// config.save_with(&fs).unwrap();
+///
+/// let backups = Backup::of("/hypr/hyprland.conf", &fs);
+/// assert_eq!(1, backups.len());
+/// assert_eq!("$mod = SUPER\n", fs.get(&backups[0].path).unwrap());
+///
+/// config.restore_with(&backups[0], &fs).unwrap();
+/// assert_eq!("SUPER", config.get_variable("mod").unwrap().value);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    /// Path of the copy
+    pub path: PathBuf,
+    /// File it is a copy of
+    pub original: PathBuf,
+    /// When the copy was made, to the second
+    pub time: SystemTime,
+}
+
+impl Backup {
+    /// Backups of the file at `path` in `fs`, oldest first
+    pub fn of(path: impl AsRef<Path>, fs: &impl ConfigFs) -> Vec<Self> {
+        let original = path.as_ref();
+        let prefix = format!("{}.bak.", original.display());
+        let mut backups: Vec<_> = fs
+            .glob(&format!("{}*", escape_glob(&prefix)))
+            .into_iter()
+            .filter_map(|path| {
+                let time = parse_time(path.to_str()?.strip_prefix(&prefix)?)?;
+                Some(Self {
+                    path,
+                    original: original.to_path_buf(),
+                    time,
+                })
+            })
+            .collect();
+        backups.sort_by_key(|backup| backup.time);
+        backups
+    }
+}
+
+impl HyprlandConfig {
+    /// Backups of the main file and of every sourced file, each file's oldest first
+    pub fn backups(&self) -> Vec<Backup> {
+        self.backups_with(&RealFs)
+    }
+
+    /// Backups in `fs` of the main file and of every sourced file
+    pub fn backups_with(&self, fs: &impl ConfigFs) -> Vec<Backup> {
+        let mut paths: Vec<&Path> = Vec::new();
+        for path in self.path.iter().chain(
+            self.sourced_files()
+                .iter()
+                .filter_map(|file| file.path.as_ref()),
+        ) {
+            if !paths.contains(&path.as_path()) {
+                paths.push(path);
+            }
+        }
+        paths
+            .into_iter()
+            .flat_map(|path| Backup::of(path, fs))
+            .collect()
+    }
+
+    /// Write a backup over the file it was made from, then load the config again
+    ///
+    /// The file is backed up first as set by [`HyprlandConfig::keep_backups`], so
+    /// restoring can be undone. Edits that weren't saved are lost.
+    pub fn restore(&mut self, backup: &Backup) -> io::Result<()> {
+        self.restore_with(backup, &RealFs)
+    }
+
+    /// Like [`HyprlandConfig::restore`], with the files in `fs`
+    pub fn restore_with(&mut self, backup: &Backup, fs: &impl ConfigFs) -> io::Result<()> {
+        let contents = fs.read(&backup.path)?;
+        if let Ok(current) = fs.read(&backup.original) {
+            back_up(&backup.original, &current, self.keep_backups, fs)?;
+        }
+        fs.write(&backup.original, &contents)?;
+        debug!("Restored {}", backup.path.display());
+
+        if self.path.is_some() {
+            *self = self.reloaded_with(fs)?;
+        }
+        Ok(())
+    }
+}
+
+/// Copy `contents`, what the file at `path` holds now, to a new backup and remove
+/// the oldest backups beyond `keep`
+pub(crate) fn back_up(
+    path: &Path,
+    contents: &str,
+    keep: usize,
+    fs: &impl ConfigFs,
+) -> io::Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    let backup = PathBuf::from(format!(
+        "{}.bak.{}",
+        path.display(),
+        format_time(SystemTime::now())
+    ));
+    // A backup from the same second already holds an older version
+    if fs.metadata(&backup).is_err() {
+        fs.write(&backup, contents)?;
+        debug!("Backed up {} to {}", path.display(), backup.display());
+    }
+
+    let backups = Backup::of(path, fs);
+    for backup in &backups[..backups.len().saturating_sub(keep)] {
+        trace!("Removing old backup {}", backup.path.display());
+        fs.remove(&backup.path)?;
+    }
+    Ok(())
+}
+
+/// Make every glob character in `text` match only itself
+fn escape_glob(text: &str) -> String {
+    if !is_glob(text) {
+        return text.to_string();
+    }
+    text.chars()
+        .map(|c| {
+            if is_glob(c.encode_utf8(&mut [0; 4])) {
+                format!("[{}]", c)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Format a time as `2026-10-18T10:00:00`, in UTC
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parse a time written by [`format_time`]
+fn parse_time(text: &str) -> Option<SystemTime> {
+    let number = |range: Range<usize>| text.get(range)?.parse::<u64>().ok();
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+
+    let days = days_from_civil(year as i64, month, day);
+    let secs = u64::try_from(days).ok()? * 86400 + hour * 3600 + minute * 60 + second;
+    let time = UNIX_EPOCH + Duration::from_secs(secs);
+    // Rejects out of range fields and anything around the numbers
+    (format_time(time) == text).then_some(time)
+}
+
+/// Year, month and day of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u64;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u64;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Number of days since 1970-01-01 of a date
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * month + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
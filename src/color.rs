@@ -0,0 +1,504 @@
+//! Colors and gradients, as written in options such as `col.active_border`
+//!
+//! ```rust
+//! use hyprparser::color::{self, ColorStyle};
+//!
+//! let color = color::parse("rgba(33ccffee)").unwrap();
+//! let (hue, saturation, lightness) = color.to_hsl();
+//! let lighter = color::Color::from_hsl(hue, saturation, lightness + 0.1, color.alpha);
+//!
+//! assert_eq!("#66d9ffee", lighter.to_css_hex());
+//! assert_eq!("0xee66d9ff", color::format_with(lighter, ColorStyle::Argb));
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{ColorError, ColorErrorKind};
+
+/// A color with float RGBA channels from 0 to 1
+///
+/// Parsed from any notation Hyprland accepts:
+///
+/// - `rgba(RRGGBBAA)` and `rgb(RRGGBB)`, or their short forms `rgba(RGBA)` and
+///   `rgb(RGB)`
+/// - `rgba(255, 128, 0, 0.5)` and `rgb(255, 128, 0)`, with the alpha from 0 to 1
+/// - `0xAARRGGBB`, or a plain decimal number holding the same
+///
+/// Colors are displayed in the notation they were parsed from, short forms and
+/// plain numbers aside, and other colors as `rgba(rrggbbaa)`. Channels are rounded
+/// to 8 bits like Hyprland does, so that parsing what was displayed gives the same
+/// color back. Two colors are equal when their 8-bit channels are.
+///
+/// ```rust
+/// use hyprparser::{Color, ColorStyle};
+///
+/// let color: Color = "rgb(33ccff)".parse().unwrap();
+///
+/// assert_eq!(Color::new(0.2, 0.8, 1.0, 1.0), color);
+/// assert_eq!("rgb(33ccff)", color.to_string());
+/// assert_eq!("rgb(51, 204, 255)", color.to_string_with(ColorStyle::RgbDecimal));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Color {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+    /// Notation the color is displayed in, `None` for `rgba(rrggbbaa)`
+    pub style: Option<ColorStyle>,
+}
+
+/// Notation a [`Color`] is written in
+///
+/// The notations without an alpha fall back to their `rgba` counterpart for
+/// colors that aren't opaque.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorStyle {
+    /// `rgba(ff8000cc)`
+    #[default]
+    Rgba,
+    /// `rgb(ff8000)`
+    Rgb,
+    /// `0xccff8000`
+    Argb,
+    /// `rgba(255, 128, 0, 0.8)`
+    RgbaDecimal,
+    /// `rgb(255, 128, 0)`
+    RgbDecimal,
+}
+
+impl Color {
+    pub const fn new(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+            style: None,
+        }
+    }
+
+    /// The same color, displayed in `style`
+    pub const fn with_style(self, style: ColorStyle) -> Self {
+        Self {
+            style: Some(style),
+            ..self
+        }
+    }
+
+    /// Color from 8-bit channels
+    pub fn from_rgba8(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self::new(
+            f32::from(red) / 255.0,
+            f32::from(green) / 255.0,
+            f32::from(blue) / 255.0,
+            f32::from(alpha) / 255.0,
+        )
+    }
+
+    fn from_argb(argb: u32) -> Self {
+        let [alpha, red, green, blue] = argb.to_be_bytes();
+        Self::from_rgba8(red, green, blue, alpha)
+    }
+
+    /// Channels rounded to the nearest 8-bit values
+    pub fn to_rgba8(self) -> [u8; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+            .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// Color from a hue in degrees and a saturation, lightness and alpha from 0 to 1
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        let (red, green, blue) = hsl_to_rgb(hue, saturation, lightness);
+        Self::new(red, green, blue, alpha)
+    }
+
+    /// Hue in degrees, saturation and lightness of the color
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        rgb_to_hsl(self.red, self.green, self.blue)
+    }
+
+    /// Color from a hue in degrees and a saturation, value and alpha from 0 to 1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> Self {
+        let (red, green, blue) = hsv_to_rgb(hue, saturation, value);
+        Self::new(red, green, blue, alpha)
+    }
+
+    /// Hue in degrees, saturation and value of the color
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        rgb_to_hsv(self.red, self.green, self.blue)
+    }
+
+    /// Parse a CSS hex color, `#rrggbb`, `#rrggbbaa` or their short forms `#rgb`
+    /// and `#rgba`
+    pub fn from_css_hex(text: &str) -> Result<Self, ColorError> {
+        let error = |kind| ColorError::new(kind, text);
+        let hex = text
+            .trim()
+            .strip_prefix('#')
+            .ok_or_else(|| error(ColorErrorKind::MissingHash))?;
+        let channels = if matches!(hex.len(), 3 | 6) { 3 } else { 4 };
+        let bytes = parse_hex(hex, channels).map_err(error)?;
+        Ok(Self::from_rgba8(
+            bytes[0],
+            bytes[1],
+            bytes[2],
+            bytes.get(3).copied().unwrap_or(255),
+        ))
+    }
+
+    /// Write the color as CSS hex, `#rrggbb` when it's opaque and `#rrggbbaa`
+    /// otherwise
+    pub fn to_css_hex(self) -> String {
+        let [red, green, blue, alpha] = self.to_rgba8();
+        if alpha == 255 {
+            format!("#{:02x}{:02x}{:02x}", red, green, blue)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", red, green, blue, alpha)
+        }
+    }
+
+    /// Write the color in `style`, whatever notation it was parsed from
+    pub fn to_string_with(self, style: ColorStyle) -> String {
+        let [red, green, blue, alpha] = self.to_rgba8();
+        match style {
+            ColorStyle::Rgb if alpha == 255 => format!("rgb({:02x}{:02x}{:02x})", red, green, blue),
+            ColorStyle::Rgba | ColorStyle::Rgb => {
+                format!("rgba({:02x}{:02x}{:02x}{:02x})", red, green, blue, alpha)
+            }
+            ColorStyle::Argb => format!("0x{:02x}{:02x}{:02x}{:02x}", alpha, red, green, blue),
+            ColorStyle::RgbDecimal if alpha == 255 => format!("rgb({}, {}, {})", red, green, blue),
+            ColorStyle::RgbaDecimal | ColorStyle::RgbDecimal => {
+                // Three decimals are enough to tell every 8-bit alpha apart
+                let alpha = (self.alpha.clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+                format!("rgba({}, {}, {}, {})", red, green, blue, alpha)
+            }
+        }
+    }
+}
+
+impl PartialEq for Color {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_rgba8() == other.to_rgba8()
+    }
+}
+
+impl From<(f32, f32, f32, f32)> for Color {
+    fn from((red, green, blue, alpha): (f32, f32, f32, f32)) -> Self {
+        Self::new(red, green, blue, alpha)
+    }
+}
+
+impl From<Color> for (f32, f32, f32, f32) {
+    fn from(color: Color) -> Self {
+        (color.red, color.green, color.blue, color.alpha)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ColorError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = |kind| ColorError::new(kind, text);
+        let color = text.trim();
+
+        if let Some(args) = color.strip_prefix("rgba(") {
+            let args = args
+                .strip_suffix(')')
+                .ok_or_else(|| error(ColorErrorKind::Unclosed))?;
+            parse_function(args, 4).map_err(error)
+        } else if let Some(args) = color.strip_prefix("rgb(") {
+            let args = args
+                .strip_suffix(')')
+                .ok_or_else(|| error(ColorErrorKind::Unclosed))?;
+            parse_function(args, 3).map_err(error)
+        } else if let Some(hex) = color.strip_prefix("0x") {
+            if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(error(ColorErrorKind::InvalidHex));
+            }
+            let argb = u32::from_str_radix(hex, 16)
+                .map_err(|_| error(ColorErrorKind::OutOfRange(hex.to_string())))?;
+            Ok(Self::from_argb(argb).with_style(ColorStyle::Argb))
+        } else if color.is_empty() {
+            Err(error(ColorErrorKind::Empty))
+        } else if let Ok(argb) = color.parse::<i64>() {
+            // Hyprland reads plain numbers as ARGB, with -1 being opaque white
+            let argb = i32::try_from(argb)
+                .map(|argb| argb as u32)
+                .or_else(|_| u32::try_from(argb))
+                .map_err(|_| error(ColorErrorKind::OutOfRange(color.to_string())))?;
+            Ok(Self::from_argb(argb).with_style(ColorStyle::Argb))
+        } else {
+            Err(error(ColorErrorKind::UnknownNotation))
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string_with(self.style.unwrap_or_default()))
+    }
+}
+
+/// Colors blended along an angle, such as `rgba(33ccffee) rgba(00ff99ee) 45deg`
+///
+/// A single color is a gradient with one stop. Each stop is displayed in its own
+/// notation, see [`Color`].
+///
+/// ```rust
+/// use hyprparser::Gradient;
+///
+/// let mut gradient: Gradient = "rgba(33ccffee) rgba(00ff99ee) 45deg".parse().unwrap();
+/// assert_eq!((2, Some(45.0)), (gradient.stops.len(), gradient.angle));
+///
+/// gradient.stops.reverse();
+/// assert_eq!("rgba(00ff99ee) rgba(33ccffee) 45deg", gradient.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub stops: Vec<Color>,
+    /// Angle in degrees, `None` when left out
+    pub angle: Option<f32>,
+}
+
+impl From<Color> for Gradient {
+    fn from(color: Color) -> Self {
+        Self {
+            stops: vec![color],
+            angle: None,
+        }
+    }
+}
+
+impl FromStr for Gradient {
+    type Err = ColorError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tokens = split_tokens(text);
+        let angle = match tokens.last().and_then(|token| token.strip_suffix("deg")) {
+            Some(angle) => {
+                let angle = angle
+                    .parse()
+                    .map_err(|_| ColorError::new(ColorErrorKind::InvalidAngle, text))?;
+                tokens.pop();
+                Some(angle)
+            }
+            None => None,
+        };
+        if tokens.is_empty() {
+            return Err(ColorError::new(ColorErrorKind::Empty, text));
+        }
+
+        let stops = tokens
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { stops, angle })
+    }
+}
+
+impl Gradient {
+    /// Write the gradient with every stop in `style`
+    pub fn to_string_with(&self, style: ColorStyle) -> String {
+        self.write(|stop| stop.to_string_with(style))
+    }
+
+    fn write(&self, mut stop: impl FnMut(&Color) -> String) -> String {
+        let mut text: Vec<_> = self.stops.iter().map(&mut stop).collect();
+        text.extend(self.angle.map(|angle| format!("{}deg", angle)));
+        text.join(" ")
+    }
+}
+
+impl fmt::Display for Gradient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.write(Color::to_string))
+    }
+}
+
+/// Parse a color in any notation Hyprland accepts, see [`Color`]
+pub fn parse(text: &str) -> Result<Color, ColorError> {
+    text.parse()
+}
+
+/// Parse a gradient, or a single color
+pub fn parse_gradient(text: &str) -> Result<Gradient, ColorError> {
+    text.parse()
+}
+
+/// Write float RGBA channels as `rgba(rrggbbaa)`, rounding each to the nearest 8-bit
+/// value
+pub fn format(red: f32, green: f32, blue: f32, alpha: f32) -> String {
+    Color::new(red, green, blue, alpha).to_string()
+}
+
+/// Write a color in `style`, see [`Color::to_string_with`]
+pub fn format_with(color: Color, style: ColorStyle) -> String {
+    color.to_string_with(style)
+}
+
+/// Convert float RGB channels to a hue in degrees, a saturation and a lightness
+pub fn rgb_to_hsl(red: f32, green: f32, blue: f32) -> (f32, f32, f32) {
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let lightness = (max + min) / 2.0;
+    let saturation = if max == min {
+        0.0
+    } else {
+        (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+    };
+    (hue(red, green, blue), saturation, lightness)
+}
+
+/// Convert a hue in degrees, a saturation and a lightness to float RGB channels
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let (saturation, lightness) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    from_chroma(hue, chroma, lightness - chroma / 2.0)
+}
+
+/// Convert float RGB channels to a hue in degrees, a saturation and a value
+pub fn rgb_to_hsv(red: f32, green: f32, blue: f32) -> (f32, f32, f32) {
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+    (hue(red, green, blue), saturation, max)
+}
+
+/// Convert a hue in degrees, a saturation and a value to float RGB channels
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> (f32, f32, f32) {
+    let (saturation, value) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+    let chroma = value * saturation;
+    from_chroma(hue, chroma, value - chroma)
+}
+
+/// Parse a CSS hex color, see [`Color::from_css_hex`]
+pub fn parse_css_hex(text: &str) -> Result<Color, ColorError> {
+    Color::from_css_hex(text)
+}
+
+/// Write a color as CSS hex, see [`Color::to_css_hex`]
+pub fn to_css_hex(color: Color) -> String {
+    color.to_css_hex()
+}
+
+/// Hue in degrees shared by HSL and HSV, 0 for grays
+fn hue(red: f32, green: f32, blue: f32) -> f32 {
+    let max = red.max(green).max(blue);
+    let delta = max - red.min(green).min(blue);
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let sector = if max == red {
+        (green - blue) / delta
+    } else if max == green {
+        (blue - red) / delta + 2.0
+    } else {
+        (red - green) / delta + 4.0
+    };
+    (sector * 60.0).rem_euclid(360.0)
+}
+
+/// RGB channels of a hue with `chroma`, `min` being added to each
+fn from_chroma(hue: f32, chroma: f32, min: f32) -> (f32, f32, f32) {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let second = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (red, green, blue) = match sector as u8 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    (red + min, green + min, blue + min)
+}
+
+/// Parse the arguments of `rgba(...)` or `rgb(...)`, either hex digits or
+/// comma-separated decimal channels
+fn parse_function(args: &str, channels: usize) -> Result<Color, ColorErrorKind> {
+    let args = args.trim();
+    let mut rgba = [1.0; 4];
+    let decimal = args.contains(',');
+
+    if decimal {
+        let components: Vec<_> = args.split(',').map(str::trim).collect();
+        if components.len() != channels {
+            return Err(ColorErrorKind::ComponentCount {
+                expected: channels,
+                found: components.len(),
+            });
+        }
+        for (index, component) in components.into_iter().enumerate() {
+            let max = if index == 3 { 1.0 } else { 255.0 };
+            let value: f32 = component
+                .parse()
+                .ok()
+                .filter(|value: &f32| value.is_finite())
+                .ok_or_else(|| ColorErrorKind::InvalidComponent(component.to_string()))?;
+            if !(0.0..=max).contains(&value) {
+                return Err(ColorErrorKind::OutOfRange(component.to_string()));
+            }
+            rgba[index] = value / max;
+        }
+    } else {
+        for (index, byte) in parse_hex(args, channels)?.into_iter().enumerate() {
+            rgba[index] = f32::from(byte) / 255.0;
+        }
+    }
+
+    let style = match (decimal, channels) {
+        (false, 4) => ColorStyle::Rgba,
+        (false, _) => ColorStyle::Rgb,
+        (true, 4) => ColorStyle::RgbaDecimal,
+        (true, _) => ColorStyle::RgbDecimal,
+    };
+    let [red, green, blue, alpha] = rgba;
+    Ok(Color::new(red, green, blue, alpha).with_style(style))
+}
+
+/// Parse one byte per channel from hex digits, or from one digit per channel in the
+/// short form, where `f80` stands for `ff8800`
+fn parse_hex(hex: &str, channels: usize) -> Result<Vec<u8>, ColorErrorKind> {
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(ColorErrorKind::InvalidHex)?;
+
+    if digits.len() == channels {
+        Ok(digits.into_iter().map(|digit| digit * 0x11).collect())
+    } else if digits.len() == channels * 2 {
+        Ok(digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect())
+    } else {
+        Err(ColorErrorKind::HexLength {
+            expected: channels * 2,
+            found: digits.len(),
+        })
+    }
+}
+
+/// Split a gradient at whitespace outside of parentheses
+fn split_tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                tokens.extend(start.take().map(|start| &text[start..index]));
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+    tokens.extend(start.map(|start| &text[start..]));
+    tokens
+}
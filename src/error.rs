@@ -0,0 +1,259 @@
+//! Errors reported while parsing and saving configurations, and parsing colors
+
+use std::ops::Range;
+use std::path::PathBuf;
+use std::{error, fmt, io};
+
+use crate::merge;
+use crate::syntax::Position;
+
+/// What went wrong while parsing
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// `}` without a matching category
+    UnexpectedClosingBrace,
+    /// Category opened with `{` that is never closed
+    UnclosedCategory(String),
+    /// Line that is neither a category nor a `key = value` assignment
+    MissingEquals,
+    /// Assignment with nothing before the `=`
+    MissingKey,
+    /// Sourced file that couldn't be read
+    Source { path: String, error: io::Error },
+    /// Sourced file that is already being read higher up the include chain
+    SourceCycle { path: String },
+}
+
+/// Error found while parsing, located in the file it comes from
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// File the error is in, `None` for configs parsed from a string
+    pub path: Option<PathBuf>,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    /// Byte range of the offending text within the file
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    pub(crate) fn at(kind: ParseErrorKind, position: Option<Position>, len: usize) -> Self {
+        let position = position.unwrap_or(Position {
+            line: 0,
+            column: 0,
+            offset: 0,
+        });
+        Self {
+            kind,
+            path: None,
+            line: position.line,
+            column: position.column,
+            span: position.offset..position.offset + len,
+        }
+    }
+
+    /// Whether the error makes the file itself invalid
+    ///
+    /// Problems with sourced files aren't fatal, Hyprland skips them and keeps going.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(
+            self.kind,
+            ParseErrorKind::Source { .. } | ParseErrorKind::SourceCycle { .. }
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::UnexpectedClosingBrace => write!(f, "unexpected `}}`"),
+            ParseErrorKind::UnclosedCategory(name) => {
+                write!(f, "category `{}` is never closed", name)
+            }
+            ParseErrorKind::MissingEquals => write!(f, "expected `key = value`"),
+            ParseErrorKind::MissingKey => write!(f, "missing key before `=`"),
+            ParseErrorKind::Source { path, error } => {
+                write!(f, "failed to read sourced file {}: {}", path, error)
+            }
+            ParseErrorKind::SourceCycle { path } => {
+                write!(f, "{} sources itself", path)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Source { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Why saving a config failed
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// Files that another program changed on disk since they were loaded
+    Conflict(Vec<Conflict>),
+}
+
+/// File changed both on disk and in memory since it was loaded or last saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: PathBuf,
+    /// Contents when the file was loaded or last saved
+    pub base: String,
+    /// Contents with the edits made in memory
+    pub ours: String,
+    /// Contents on disk now
+    pub theirs: String,
+}
+
+impl Conflict {
+    /// Three-way merge of the edits made in memory onto the contents on disk, line
+    /// by line
+    ///
+    /// `None` when both sides changed the same or adjacent lines differently.
+    ///
+    /// ```rust
+    /// use hyprparser::Conflict;
+    ///
+    /// let conflict = Conflict {
+    ///     path: "hyprland.conf".into(),
+    ///     base: "gaps_in = 5\nborder_size = 1\n".to_string(),
+    ///     ours: "gaps_in = 10\nborder_size = 1\n".to_string(),
+    ///     theirs: "gaps_in = 5\nborder_size = 1\nrounding = 8\n".to_string(),
+    /// };
+    ///
+    /// assert_eq!(
+    ///     Some("gaps_in = 10\nborder_size = 1\nrounding = 8\n".to_string()),
+    ///     conflict.merge()
+    /// );
+    /// ```
+    pub fn merge(&self) -> Option<String> {
+        merge::merge(&self.base, &self.ours, &self.theirs)
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Conflict(conflicts) => {
+                let paths: Vec<_> = conflicts
+                    .iter()
+                    .map(|conflict| conflict.path.display().to_string())
+                    .collect();
+                write!(f, "changed on disk since loaded: {}", paths.join(", "))
+            }
+        }
+    }
+}
+
+impl error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SaveError::Io(error) => Some(error),
+            SaveError::Conflict(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<SaveError> for io::Error {
+    fn from(error: SaveError) -> Self {
+        match error {
+            SaveError::Io(error) => error,
+            error => io::Error::other(error),
+        }
+    }
+}
+
+/// What is wrong with a color or gradient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorErrorKind {
+    /// Nothing but whitespace, or only an angle
+    Empty,
+    /// Neither `rgba(...)`, `rgb(...)`, `0x...` nor a number
+    UnknownNotation,
+    /// `rgba(` or `rgb(` without the closing `)`
+    Unclosed,
+    /// CSS hex color without its leading `#`
+    MissingHash,
+    /// Wrong number of hex digits, `expected` being the length of the long form
+    HexLength { expected: usize, found: usize },
+    /// Character that isn't a hex digit
+    InvalidHex,
+    /// Wrong number of comma-separated channels
+    ComponentCount { expected: usize, found: usize },
+    /// Channel that isn't a number
+    InvalidComponent(String),
+    /// Number too large or too small, channels going from 0 to 255 and the alpha
+    /// from 0 to 1
+    OutOfRange(String),
+    /// Gradient angle that isn't a number followed by `deg`
+    InvalidAngle,
+}
+
+/// Error returned when parsing a [`Color`](crate::Color) or
+/// [`Gradient`](crate::Gradient) fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorError {
+    pub kind: ColorErrorKind,
+    /// Text that failed to parse
+    pub text: String,
+}
+
+impl ColorError {
+    pub(crate) fn new(kind: ColorErrorKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid color `{}`: ", self.text)?;
+        match &self.kind {
+            ColorErrorKind::Empty => write!(f, "no color given"),
+            ColorErrorKind::UnknownNotation => {
+                write!(f, "expected `rgba(...)`, `rgb(...)`, `0x...` or a number")
+            }
+            ColorErrorKind::Unclosed => write!(f, "missing closing `)`"),
+            ColorErrorKind::MissingHash => write!(f, "CSS hex colors start with `#`"),
+            ColorErrorKind::HexLength { expected, found } => write!(
+                f,
+                "expected {} hex digits, or {} in the short form, found {}",
+                expected,
+                expected / 2,
+                found
+            ),
+            ColorErrorKind::InvalidHex => write!(f, "not a hex number"),
+            ColorErrorKind::ComponentCount { expected, found } => {
+                write!(f, "expected {} channels, found {}", expected, found)
+            }
+            ColorErrorKind::InvalidComponent(component) => {
+                write!(f, "`{}` is not a number", component)
+            }
+            ColorErrorKind::OutOfRange(number) => write!(f, "`{}` is out of range", number),
+            ColorErrorKind::InvalidAngle => write!(f, "the angle must be a number of degrees"),
+        }
+    }
+}
+
+impl error::Error for ColorError {}
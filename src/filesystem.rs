@@ -0,0 +1,382 @@
+//! Filesystems configs are loaded from and saved to
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::source::{glob_match, is_glob};
+
+/// What [`ConfigFs::metadata`] reports about a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Size in bytes
+    pub len: u64,
+    /// Last modification time, when the filesystem records one
+    pub modified: Option<SystemTime>,
+}
+
+/// A file as it was when it was loaded or last saved, to notice other programs
+/// changing it in the meantime
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    hash: u64,
+    /// Contents, the base of three-way merges
+    pub(crate) text: String,
+}
+
+impl Snapshot {
+    pub(crate) fn new(text: String) -> Self {
+        Self {
+            hash: hash(&text),
+            text,
+        }
+    }
+
+    /// Whether the file, now holding `current`, was changed since
+    ///
+    /// Only the contents are compared, since editors, `cp -p` and the like keep
+    /// the modification time, and a file that was only touched is unchanged.
+    pub(crate) fn is_changed(&self, current: &str) -> bool {
+        hash(current) != self.hash
+    }
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Filesystem configs are read from and written to
+///
+/// [`RealFs`] is the actual filesystem and [`MemoryFs`] keeps files in memory,
+/// for tests and previews. Other implementations can serve configs from a git
+/// tree, an archive and so on.
+pub trait ConfigFs {
+    /// Read a whole file
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Replace a file's contents, creating it if needed
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    /// Existing paths matching a glob pattern with `*`, `?` and `[...]`, sorted
+    fn glob(&self, pattern: &str) -> Vec<PathBuf>;
+
+    /// Absolute path of a file, with links and `.`/`..` resolved
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Delete a file
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// Write several files as a unit
+    ///
+    /// The default implementation writes them one after another.
+    fn write_all(&self, files: &[(&Path, String)]) -> io::Result<()> {
+        for (path, contents) in files {
+            self.write(path, contents)?;
+        }
+        Ok(())
+    }
+}
+
+/// The actual filesystem
+///
+/// Writes go to a temporary file next to the target, keeping its permissions,
+/// which then replaces it. [`ConfigFs::write_all`] only replaces the targets once
+/// every temporary file is written, so running out of space or crashing never
+/// leaves a file truncated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl ConfigFs for RealFs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.write_all(&[(path, contents.to_string())])
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<PathBuf> {
+        let mut paths = vec![PathBuf::new()];
+
+        for component in Path::new(pattern).components() {
+            match component {
+                Component::Normal(part) if is_glob(&part.to_string_lossy()) => {
+                    let part = part.to_string_lossy();
+                    paths = paths
+                        .iter()
+                        .filter_map(|dir| {
+                            let dir = if dir.as_os_str().is_empty() {
+                                Path::new(".")
+                            } else {
+                                dir
+                            };
+                            fs::read_dir(dir).ok()
+                        })
+                        .flatten()
+                        .filter_map(Result::ok)
+                        .filter(|entry| glob_match(&part, &entry.file_name().to_string_lossy()))
+                        .map(|entry| entry.path())
+                        .map(|path| {
+                            path.strip_prefix(".")
+                                .map(Path::to_path_buf)
+                                .unwrap_or(path)
+                        })
+                        .collect();
+                }
+                component => {
+                    for path in &mut paths {
+                        path.push(component);
+                    }
+                }
+            }
+        }
+
+        paths.retain(|path| path.exists());
+        paths.sort();
+        paths
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileMetadata {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn write_all(&self, files: &[(&Path, String)]) -> io::Result<()> {
+        let mut staged = Vec::new();
+        for (path, contents) in files {
+            match stage(path, contents) {
+                Ok(paths) => staged.push(paths),
+                Err(error) => {
+                    error!("Failed to write {}: {}", path.display(), error);
+                    discard(&staged);
+                    return Err(error);
+                }
+            }
+        }
+
+        for (index, (temp, target)) in staged.iter().enumerate() {
+            if let Err(error) = fs::rename(temp, target) {
+                error!("Failed to replace {}: {}", target.display(), error);
+                discard(&staged[index..]);
+                return Err(error);
+            }
+            debug!("Saved {}", target.display());
+        }
+        Ok(())
+    }
+}
+
+/// Write `contents` to a temporary file next to `path`, returning the temporary
+/// file and the file it should replace
+///
+/// Symlinks are followed, so that the file they point to is replaced rather than
+/// the link itself.
+fn stage(path: &Path, contents: &str) -> io::Result<(PathBuf, PathBuf)> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = target
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the path has no file name"))?;
+
+    // Each save gets a temporary file of its own, even several at once from the
+    // same process
+    let (temp, mut file) = loop {
+        let temp = target.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            name.to_string_lossy(),
+            process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        match File::options().write(true).create_new(true).open(&temp) {
+            Ok(file) => break (temp, file),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    };
+
+    match write_synced(&mut file, &target, contents) {
+        Ok(()) => Ok((temp, target)),
+        Err(error) => {
+            let _ = fs::remove_file(&temp);
+            Err(error)
+        }
+    }
+}
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn write_synced(file: &mut File, target: &Path, contents: &str) -> io::Result<()> {
+    file.write_all(contents.as_bytes())?;
+    if let Ok(metadata) = fs::metadata(target) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+fn discard(staged: &[(PathBuf, PathBuf)]) {
+    for (temp, _) in staged {
+        let _ = fs::remove_file(temp);
+    }
+}
+
+/// Files kept in memory, keyed by path
+///
+/// Paths are taken as they are, apart from `.` and `..` being resolved.
+///
+/// ```rust
+/// use hyprparser::{HyprlandConfig, MemoryFs};
+///
+/// let fs = MemoryFs::new()
+///     .with_file("/hypr/hyprland.conf", "source = looks.conf\n")
+///     .with_file("/hypr/looks.conf", "general {\n    gaps_in = 5\n}\n");
+///
+/// let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+/// config.add_entry("general", "gaps_in = 10");
+/// config.save_with(&fs).unwrap();
+///
+/// assert_eq!(
+///     Some("general {\n    gaps_in = 10\n}\n".to_string()),
+///     fs.get("/hypr/looks.conf")
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<PathBuf, (String, SystemTime)>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder form of [`MemoryFs::insert`]
+    pub fn with_file(self, path: impl AsRef<Path>, contents: &str) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    /// Add a file, replacing any file already at `path`
+    pub fn insert(&self, path: impl AsRef<Path>, contents: &str) {
+        self.lock().insert(
+            normalize(path.as_ref()),
+            (contents.to_string(), SystemTime::now()),
+        );
+    }
+
+    /// Contents of the file at `path`
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.lock()
+            .get(&normalize(path.as_ref()))
+            .map(|(contents, _)| contents.clone())
+    }
+
+    /// Every file path, sorted
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, (String, SystemTime)>> {
+        self.files.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl ConfigFs for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.get(path).ok_or_else(|| not_found(path))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<PathBuf> {
+        let pattern = normalize(Path::new(pattern));
+        let pattern: Vec<_> = pattern.components().collect();
+        self.lock()
+            .keys()
+            .filter(|path| {
+                let components: Vec<_> = path.components().collect();
+                components.len() == pattern.len()
+                    && components.iter().zip(&pattern).all(|(component, pattern)| {
+                        match (component, pattern) {
+                            (Component::Normal(name), Component::Normal(part)) => {
+                                glob_match(&part.to_string_lossy(), &name.to_string_lossy())
+                            }
+                            (component, pattern) => component == pattern,
+                        }
+                    })
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = normalize(path);
+        if self.lock().contains_key(&path) {
+            Ok(path)
+        } else {
+            Err(not_found(&path))
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.lock()
+            .get(&normalize(path))
+            .map(|(contents, modified)| FileMetadata {
+                len: contents.len() as u64,
+                modified: Some(*modified),
+            })
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.lock()
+            .remove(&normalize(path))
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+/// Resolve `.` and `..` in a path without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} doesn't exist", path.display()),
+    )
+}
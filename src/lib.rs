@@ -10,36 +10,104 @@
 //! [Hyprland's documentation](https://wiki.hyprland.org/Configuring)
 //!
 //! # Example usage
-//! ```rust
-//! use hyprparser::parse_config;
-//! use std::{env, fs, path::Path};
+//! ```rust,no_run
+//! use hyprparser::{Dirs, HyprlandConfig};
 //!
-//! let config_path = Path::new(&env::var("XDG_CONFIG_HOME").unwrap()).join("hypr/hyprland.conf");
-//! let config_str = fs::read_to_string(&config_path).expect("Failed to read the file");
+//! let config_path = Dirs::from_env().config.join("hyprland.conf");
+//! let mut config = HyprlandConfig::load(&config_path).expect("Failed to read the file");
 //!
-//! let mut parsed_config = parse_config(&config_str);
+//! config.add_entry("decoration", "rounding = 10");
+//! config.add_entry("decoration.blur", "enabled = true");
+//! config.add_entry("decoration.blur", "size = 10");
+//! config.add_entry_headless("$terminal", "kitty");
 //!
-//! parsed_config.add_entry("decoration", "rounding = 10");
-//! parsed_config.add_entry("decoration.blur", "enabled = true");
-//! parsed_config.add_entry("decoration.blur", "size = 10");
-//! parsed_config.add_entry_headless("$terminal", "kitty");
-//!
-//! let updated_config_str = parsed_config.to_string();
-//!
-//! fs::write(&config_path, updated_config_str).expect("Failed to write the file");
+//! config.save().expect("Failed to write the file");
 //! ```
 
-use std::collections::HashMap;
-use std::{env, fmt, fs};
+#[macro_use]
+mod macros;
+
+mod backup;
+pub mod color;
+mod error;
+mod filesystem;
+mod merge;
+mod origin;
+mod query;
+mod save;
+mod source;
+mod syntax;
+mod value;
+mod variables;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use backup::Backup;
+pub use color::{Color, ColorStyle, Gradient};
+pub use error::{ColorError, ColorErrorKind, Conflict, ParseError, ParseErrorKind, SaveError};
+pub use filesystem::{ConfigFs, FileMetadata, MemoryFs, RealFs};
+pub use origin::Origin;
+pub use query::{Entry, EntryChange};
+pub use source::{Dirs, Include, Selector, SourcedFile};
+pub use value::{Gaps, Value};
+pub use variables::Variable;
+#[cfg(feature = "watch")]
+pub use watch::{ConfigEvent, ConfigWatcher};
+
+use filesystem::Snapshot;
+pub use syntax::{
+    AddMode, Assignment, Category, CategoryPath, Document, Iter, Node, Position, Trivia, Unparsed,
+};
+use variables::Scope;
+
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
+
+/// How new entries are written into the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryStyle {
+    /// Inside category blocks, creating them when needed
+    ///
+    /// ```conf
+    /// decoration {
+    ///     blur {
+    ///         enabled = true
+    ///     }
+    /// }
+    /// ```
+    #[default]
+    Block,
+    /// As one line with the category path separated by colons
+    ///
+    /// ```conf
+    /// decoration:blur:enabled = true
+    /// ```
+    Colon,
+}
 
 /// Core structure of the config
 #[derive(Debug, Default)]
 pub struct HyprlandConfig {
-    pub content: Vec<String>,
-    pub sections: HashMap<String, (usize, usize)>,
-    pub sourced_content: Vec<Vec<String>>,
-    pub sourced_sections: HashMap<String, (usize, usize)>,
-    pub sourced_paths: Vec<String>,
+    /// Path of the main file, `None` for configs parsed from a string
+    pub path: Option<PathBuf>,
+    /// Directories `~` and relative sources are resolved against
+    pub dirs: Dirs,
+    pub content: Document,
+    /// Problems found while parsing, in the main file and every sourced file
+    pub diagnostics: Vec<ParseError>,
+    /// Whether removing entries also removes the categories they leave empty
+    pub remove_empty_categories: bool,
+    /// How new entries are written
+    pub entry_style: EntryStyle,
+    /// How many backups of each file saving keeps, see [`Backup`], 0 for none
+    pub keep_backups: usize,
+    /// Files loaded through `source` directives, see [`HyprlandConfig::sourced_files`]
+    sourced: Vec<SourcedFile>,
+    /// The main file as it was on disk when it was loaded or last saved
+    loaded: Option<Snapshot>,
+    /// Every path and glob pattern `source` directives pointed to, expanded,
+    /// including those that matched nothing
+    source_paths: Vec<PathBuf>,
 }
 
 impl HyprlandConfig {
@@ -48,241 +116,244 @@ impl HyprlandConfig {
     }
 
     /// Parse one configuration file
+    ///
+    /// Files it sources are loaded too, recursively, with glob patterns expanded in
+    /// sorted order. Relative sources are resolved against the directory of
+    /// [`HyprlandConfig::path`], or [`Dirs::config`] without one. A sourced file
+    /// parsed this way has no path. Malformed lines are kept as they are and
+    /// reported in [`HyprlandConfig::diagnostics`].
     pub fn parse(&mut self, config_str: &str, sourced: bool) {
-        let mut section_stack = Vec::new();
-        let mut sourced_content: Vec<String> = Vec::new();
-        let source_index = if sourced {
-            self.sourced_content.len()
-        } else {
-            0
-        };
-
-        let mut env_vars = HashMap::new();
-        let home = env::var("HOME").unwrap_or_default();
-        env_vars.insert("HOME".to_string(), home.clone());
+        self.parse_with(config_str, sourced, &RealFs);
+    }
 
-        println!("Parsing env vars from config:");
-        for line in config_str.lines() {
-            let trimmed = line.trim();
-            if let Some((var, val)) = trimmed
-                .split_once('=')
-                .map(|(v, p)| (v.trim(), p.split('#').next().unwrap_or(p).trim()))
-            {
-                if let Some(stripped) = var.strip_prefix('$') {
-                    println!("Found env var: {} = {}", var, val);
-                    let mut expanded_val = val.to_string();
-                    for (existing_var, existing_val) in &env_vars {
-                        expanded_val =
-                            expanded_val.replace(&format!("${}", existing_var), existing_val);
-                    }
-                    env_vars.insert(stripped.to_string(), expanded_val);
-                    continue;
-                }
+    /// Parse one configuration file, like [`HyprlandConfig::parse`], reading the
+    /// files it sources from `fs`
+    pub fn parse_with(&mut self, config_str: &str, sourced: bool, fs: &impl ConfigFs) {
+        let mut loader = Loader {
+            fs,
+            scope: Scope::default(),
+            chain: self
+                .path
+                .iter()
+                .map(|path| fs.canonicalize(path).unwrap_or_else(|_| path.clone()))
+                .collect(),
+        };
+        if sourced {
+            self.sourced.push(SourcedFile::new(None, None, None));
+            let index = self.sourced.len() - 1;
+            self.sourced[index].content = self.parse_file(config_str, Some(index), &mut loader);
+        } else {
+            let document = self.parse_file(config_str, None, &mut loader);
+            for node in document.nodes {
+                self.content.push(node);
             }
         }
-        println!("Collected env vars: {:?}", env_vars);
-
-        for (i, line) in config_str.lines().enumerate() {
-            let trimmed = line.trim();
-
-            if trimmed.starts_with("source") && !sourced {
-                if let Some(path) = trimmed
-                    .split_once('=')
-                    .map(|(_, p)| p.split('#').next().unwrap_or(p).trim())
-                {
-                    println!("Processing source path: {}", path);
-                    let mut expanded_path = path.to_string();
-
-                    for (var, val) in &env_vars {
-                        let var_pattern = format!("${}", var);
-                        println!("Replacing {} with {}", var_pattern, val);
-                        expanded_path = expanded_path.replace(&var_pattern, val);
-                    }
-                    println!("After env var expansion: {}", expanded_path);
-
-                    if !expanded_path.starts_with('/') && !expanded_path.starts_with('~') {
-                        expanded_path = format!("{}/.config/hypr/{}", home, expanded_path);
-                    } else {
-                        expanded_path = expanded_path.replacen("~", &home, 1);
-                    }
-                    println!("Final expanded path: {}", expanded_path);
-
-                    match fs::read_to_string(&expanded_path) {
-                        Ok(content) => {
-                            println!("Successfully read sourced file");
-                            self.parse(&content, true);
-                            self.sourced_paths.push(expanded_path);
-                        }
-                        Err(e) => println!("Failed to read file: {}", e),
-                    }
+    }
+
+    /// Parse the main file or the sourced file at `file`, loading the files it sources
+    fn parse_file<F: ConfigFs>(
+        &mut self,
+        config_str: &str,
+        file: Option<usize>,
+        loader: &mut Loader<F>,
+    ) -> Document {
+        let file_path = self.file_path(file).map(Path::to_path_buf);
+        let (document, diagnostics) = Document::parse_with_diagnostics(config_str);
+        self.diagnostics
+            .extend(diagnostics.into_iter().map(|mut error| {
+                error.path.clone_from(&file_path);
+                error
+            }));
+
+        for node in document.iter() {
+            match node {
+                Node::Variable(variable) => {
+                    let value = loader.scope.define(&variable.key[1..], &variable.value);
+                    trace!("Found variable: {} = {}", variable.key, value);
                 }
-            } else if trimmed.ends_with('{') {
-                let section_name = trimmed.trim_end_matches('{').trim().to_string();
-                section_stack.push((section_name, i));
-            } else if trimmed == "}" && !section_stack.is_empty() {
-                let (name, start) = section_stack.pop().unwrap();
-                let full_name = section_stack
-                    .iter()
-                    .map(|(n, _)| n.as_str())
-                    .chain(std::iter::once(name.as_str()))
-                    .collect::<Vec<_>>()
-                    .join(".");
-                if sourced {
-                    self.sourced_sections
-                        .insert(format!("{}_{}", full_name, source_index), (start, i));
-                } else {
-                    self.sections.insert(full_name, (start, i));
+                Node::Source(source) => {
+                    let diagnostics = self.load_source(source, file, loader);
+                    self.diagnostics
+                        .extend(diagnostics.into_iter().map(|mut error| {
+                            error.path.clone_from(&file_path);
+                            error
+                        }));
                 }
+                _ => {}
             }
-            if sourced {
-                sourced_content.push(line.to_string());
-            } else {
-                self.content.push(line.to_string());
-            }
-        }
-        if sourced {
-            self.sourced_content.push(sourced_content);
         }
+
+        document
     }
 
-    /// Add an entry to a mutable `HyprlandConfig`
-    pub fn add_entry(&mut self, category: &str, entry: &str) {
-        let parts: Vec<&str> = category.split('.').collect();
-        let parent_category = if parts.len() > 1 {
-            parts[..parts.len() - 1].join(".")
+    /// Load every file a `source` directive in `file` points to
+    fn load_source<F: ConfigFs>(
+        &mut self,
+        source: &Assignment,
+        file: Option<usize>,
+        loader: &mut Loader<F>,
+    ) -> Vec<ParseError> {
+        let path = source.value.as_str();
+        debug!("Processing source path: {}", path);
+
+        let expanded_path = loader
+            .scope
+            .expand_with_home(path, &self.dirs.home.to_string_lossy());
+        trace!("After variable expansion: {}", expanded_path);
+
+        let expanded_path = self
+            .dirs
+            .resolve(&expanded_path, self.file_path(file))
+            .to_string_lossy()
+            .into_owned();
+        debug!("Final expanded path: {}", expanded_path);
+
+        self.source_paths.push(PathBuf::from(&expanded_path));
+
+        let line = source.trivia.position.map(|position| position.line);
+        let len = source.key.len() + source.separator.len() + source.value.len();
+        let error_at = |kind| ParseError::at(kind, source.trivia.position, len);
+
+        let paths = if source::is_glob(&expanded_path) {
+            let paths = loader.fs.glob(&expanded_path);
+            if paths.is_empty() {
+                warn!("No file matches source pattern {}", expanded_path);
+                return vec![error_at(ParseErrorKind::Source {
+                    path: expanded_path,
+                    error: io::Error::new(io::ErrorKind::NotFound, "no file matches the pattern"),
+                })];
+            }
+            paths
         } else {
-            category.to_string()
+            vec![PathBuf::from(&expanded_path)]
         };
 
-        if let Some((source_index, _)) = self.find_sourced_section(&parent_category) {
-            let section_key = format!("{}_{}", parent_category, source_index);
-            let (start, mut end) = *self.sourced_sections.get(&section_key).unwrap();
-            let depth = parent_category.matches('.').count();
-            let key = entry.split('=').next().unwrap().trim();
-
-            let mut should_update_sections = false;
-            let mut content_updated = String::new();
-
-            if let Some(sourced_content) = self.sourced_content.get_mut(source_index) {
-                let subcategory_key = format!("{}_{}", category, source_index);
-
-                if parts.len() > 1 && !self.sourced_sections.contains_key(&subcategory_key) {
-                    let last_part = parts.last().unwrap();
-                    let section_start = format!("{}{} {{", "    ".repeat(depth + 1), last_part);
-                    let section_end = format!("{}}}", "    ".repeat(depth + 1));
-
-                    if end > 0
-                        && end <= sourced_content.len()
-                        && !sourced_content[end - 1].trim().is_empty()
-                    {
-                        sourced_content.insert(end, String::new());
-                        end += 1;
-                    }
-
-                    sourced_content.insert(end, section_start);
-                    sourced_content
-                        .insert(end + 1, format!("{}{}", "    ".repeat(depth + 2), entry));
-                    sourced_content.insert(end + 2, section_end);
-
-                    self.sourced_sections
-                        .insert(subcategory_key, (end + 1, end + 1));
-                    should_update_sections = true;
-                } else if let Some(&(sub_start, sub_end)) =
-                    self.sourced_sections.get(&subcategory_key)
-                {
-                    let parent_category = if parts.len() > 1 {
-                        parts[..parts.len()].join(".")
-                    } else {
-                        category.to_string()
-                    };
-                    let depth = parent_category.matches('.').count();
-
-                    let formatted_entry = format!("{}{}", "    ".repeat(depth + 1), entry);
-                    let existing_line = sourced_content[sub_start..=sub_end]
-                        .iter()
-                        .position(|line| line.trim().starts_with(key));
-
-                    match existing_line {
-                        Some(line_num) => {
-                            sourced_content[sub_start + line_num] = formatted_entry;
-                        }
-                        None => {
-                            sourced_content.insert(sub_end, formatted_entry);
-                            should_update_sections = true;
-                        }
-                    }
-                } else {
-                    let formatted_entry = format!("{}{}", "    ".repeat(depth + 1), entry);
-                    let existing_line = sourced_content[start..=end]
-                        .iter()
-                        .position(|line| line.trim().starts_with(key));
-
-                    match existing_line {
-                        Some(line_num) => {
-                            sourced_content[start + line_num] = formatted_entry;
-                        }
-                        None => {
-                            sourced_content.insert(end, formatted_entry);
-                            should_update_sections = true;
-                        }
-                    }
-                }
-
-                content_updated = sourced_content.join("\n");
-            }
-
-            if should_update_sections {
-                self.update_sourced_sections(source_index, end, 1);
+        let mut diagnostics = Vec::new();
+        for path in paths {
+            let canonical = loader
+                .fs
+                .canonicalize(&path)
+                .unwrap_or_else(|_| path.clone());
+            let path = path.to_string_lossy().into_owned();
+            if loader.chain.contains(&canonical) {
+                warn!("Not sourcing {} again, it sources itself", path);
+                diagnostics.push(error_at(ParseErrorKind::SourceCycle { path }));
+                continue;
             }
 
-            if let Some(sourced_path) = self.sourced_paths.get(source_index) {
-                if !sourced_path.is_empty() {
-                    if let Err(e) = fs::write(sourced_path, content_updated) {
-                        eprintln!("Failed to write to sourced file {}: {}", sourced_path, e);
-                    }
+            match loader.fs.read(Path::new(&path)) {
+                Ok(content) => {
+                    debug!("Successfully read sourced file {}", path);
+                    self.sourced
+                        .push(SourcedFile::new(Some(PathBuf::from(&path)), file, line));
+                    let index = self.sourced.len() - 1;
+                    loader.chain.push(canonical);
+                    self.sourced[index].content = self.parse_file(&content, Some(index), loader);
+                    self.sourced[index].loaded = Some(Snapshot::new(content));
+                    loader.chain.pop();
+                }
+                Err(error) => {
+                    warn!("Failed to read sourced file {}: {}", path, error);
+                    diagnostics.push(error_at(ParseErrorKind::Source { path, error }));
                 }
             }
-            return;
         }
+        diagnostics
+    }
+
+    /// Path of the main file or of the sourced file at `file`
+    pub(crate) fn file_path(&self, file: Option<usize>) -> Option<&Path> {
+        match file {
+            Some(index) => self.sourced[index].path.as_deref(),
+            None => self.path.as_deref(),
+        }
+    }
 
-        let parts: Vec<&str> = category.split('.').collect();
-        let mut current_section = String::new();
-        let mut insert_pos = self.content.len();
+    /// Contents of the main file or of the sourced file at `file`
+    pub(crate) fn document(&self, file: Option<usize>) -> &Document {
+        match file {
+            Some(index) => &self.sourced[index].content,
+            None => &self.content,
+        }
+    }
 
-        for (depth, (i, part)) in parts.iter().enumerate().enumerate() {
-            if i > 0 {
-                current_section.push('.');
-            }
-            current_section.push_str(part);
+    pub(crate) fn document_mut(&mut self, file: Option<usize>) -> &mut Document {
+        match file {
+            Some(index) => &mut self.sourced[index].content,
+            None => &mut self.content,
+        }
+    }
 
-            if !self.sections.contains_key(&current_section) {
-                self.create_category(&current_section, depth, &mut insert_pos);
+    /// Add an entry to a mutable `HyprlandConfig`
+    ///
+    /// An existing entry with exactly the same key directly inside the category is
+    /// replaced, see [`HyprlandConfig::add_entry_with`] to append instead.
+    pub fn add_entry(&mut self, category: &str, entry: &str) {
+        self.add_entry_with(category, entry, AddMode::Replace);
+    }
+
+    /// Add an entry to a mutable `HyprlandConfig`, choosing what happens to an
+    /// existing entry with the same key
+    ///
+    /// ```rust
+    /// use hyprparser::{parse_config, AddMode};
+    ///
+    /// let mut config = parse_config("input {\n    kb_layout = us\n}\n");
+    /// config.add_entry_with("input", "kb_layout = de", AddMode::Append);
+    ///
+    /// assert_eq!(2, config.get_all("input.kb_layout").len());
+    /// ```
+    pub fn add_entry_with(&mut self, category: &str, entry: &str, mode: AddMode) {
+        if let Some(Node::Assignment(mut new)) = Node::from_line(entry, 0) {
+            if mode == AddMode::Replace && self.replace_effective(category, &new) {
+                return;
             }
 
-            let &(start, end) = self.sections.get(&current_section).unwrap();
-            insert_pos = end;
-
-            if i == parts.len() - 1 {
-                let key = entry.split('=').next().unwrap().trim();
-                let existing_line = self.content[start..=end]
-                    .iter()
-                    .position(|line| line.trim().starts_with(key))
-                    .map(|pos| start + pos);
-
-                let formatted_entry = format!("{}{}", "    ".repeat(depth + 1), entry);
-
-                match existing_line {
-                    Some(line_num) => {
-                        self.content[line_num] = formatted_entry;
-                    }
-                    None => {
-                        self.content.insert(end, formatted_entry);
-                        self.update_sections(end, 1);
-                    }
+            if self.entry_style == EntryStyle::Colon {
+                if !category.is_empty() {
+                    let prefix = syntax::split_path(category).join(":");
+                    new.key = format!("{}:{}", prefix, new.key);
                 }
+                self.content.push(Node::Assignment(new));
                 return;
             }
         }
+
+        let parts = syntax::split_path(category);
+        let parent_category = match parts[..] {
+            [.., _, last] => &category[..category.len() - last.len() - 1],
+            _ => category,
+        };
+
+        if let Some(source_index) = self.find_sourced_section(parent_category) {
+            self.sourced[source_index]
+                .content
+                .add_entry_with(category, entry, mode);
+            self.mark_dirty(source_index);
+            return;
+        }
+
+        self.content.add_entry_with(category, entry, mode);
+    }
+
+    /// Overwrite the value of the effective assignment of the entry's key, wherever
+    /// and in whichever style it is written
+    fn replace_effective(&mut self, category: &str, new: &Assignment) -> bool {
+        let name = query::full_name(category, &new.key);
+        let Some((node, file)) = self.effective_mut(|path, node| {
+            matches!(node, Node::Assignment(assignment) if path.matches(&name, &assignment.key))
+        }) else {
+            return false;
+        };
+
+        if let Node::Assignment(existing) = node {
+            existing.separator.clone_from(&new.separator);
+            existing.value.clone_from(&new.value);
+        }
+        if let Some(index) = file {
+            self.mark_dirty(index);
+        }
+        true
     }
 
     /// Add a headless entry to a mutable `HyprlandConfig`
@@ -293,150 +364,167 @@ impl HyprlandConfig {
     /// ```
     pub fn add_entry_headless(&mut self, key: &str, value: &str) {
         if key.is_empty() && value.is_empty() {
-            self.content.push(String::new());
+            self.content.push(Node::blank());
         } else {
             let entry = format!("{} = {}", key, value);
-            if !self.content.iter().any(|line| line.trim() == entry.trim()) {
-                self.content.push(entry);
+            if !self
+                .content
+                .nodes
+                .iter()
+                .any(|node| node.to_string().trim() == entry.trim())
+            {
+                if let Some(node) = Node::from_line(&entry, 0) {
+                    self.content.push(node);
+                }
             }
         }
     }
 
-    /// Add a [sourced config file](https://wiki.hyprland.org/Configuring/Keywords/#sourcing-multi-file)
-    pub fn add_sourced(&mut self, config: Vec<String>) {
-        self.sourced_content.push(config);
-        self.sourced_paths.push(String::new());
-    }
+    /// Remove the effective assignment of `key` in `category`, the one
+    /// [`HyprlandConfig::get`] returns
+    ///
+    /// Returns whether an entry was removed.
+    pub fn remove_entry(&mut self, category: &str, key: &str) -> bool {
+        let name = query::full_name(category, key);
+        let mut is_entry = |path: &CategoryPath, node: &Node| matches!(node, Node::Assignment(assignment) if path.matches(&name, &assignment.key));
+        let Some((file, occurrence)) = self.find_effective(&mut is_entry) else {
+            return false;
+        };
 
-    fn update_sections(&mut self, pos: usize, offset: usize) {
-        for (start, end) in self.sections.values_mut() {
-            if *start >= pos {
-                *start += offset;
-                *end += offset;
-            } else if *end >= pos {
-                *end += offset;
+        let mut seen = 0;
+        self.remove_from(file, |path, node| {
+            if !is_entry(path, node) {
+                return false;
             }
-        }
+            seen += 1;
+            seen - 1 == occurrence
+        });
+        true
     }
 
-    fn update_sourced_sections(&mut self, source_index: usize, pos: usize, offset: usize) {
-        for ((_, (start, end)), sourced_path) in self
-            .sourced_sections
-            .iter_mut()
-            .filter(|(_, (start, _))| *start >= pos)
-            .zip(self.sourced_paths.iter().skip(source_index))
-        {
-            if !sourced_path.is_empty() {
-                if *start >= pos {
-                    *start += offset;
-                    *end += offset;
-                } else if *end >= pos {
-                    *end += offset;
-                }
-            }
-        }
+    /// Remove every assignment of `key` in `category`, returning how many were removed
+    pub fn remove_all(&mut self, category: &str, key: &str) -> usize {
+        let name = query::full_name(category, key);
+        self.remove_everywhere(|path, node| {
+            matches!(node, Node::Assignment(assignment) if path.matches(&name, &assignment.key))
+        })
     }
 
-    /// Parse a color from Hyprland's config into float RGBA values
+    /// Remove every category at the dotted path, with everything inside it
     ///
-    /// Examples:
-    /// ```rust
-    /// use hyprparser::HyprlandConfig;
+    /// Returns how many categories were removed.
+    pub fn remove_category(&mut self, path: &str) -> usize {
+        self.remove_everywhere(|parent, node| match node {
+            Node::Category(category) => {
+                let mut parent = parent.clone();
+                parent.push(category);
+                parent.matches(path, "")
+            }
+            _ => false,
+        })
+    }
+
+    /// Remove headless entries with the given key whose value matches the predicate
     ///
-    /// let config = HyprlandConfig::new();
+    /// Returns how many entries were removed.
     ///
-    /// let rgba = config.parse_color("rgba(1E4632FF)");
-    /// let rgb = config.parse_color("rgb(1E4632)");
-    /// let argb = config.parse_color("0xFF1E4632");
+    /// ```rust
+    /// use hyprparser::parse_config;
     ///
-    /// let expected = Some((0.11764706, 0.27450982, 0.19607843, 1.0));
+    /// let mut config = parse_config("bind = SUPER, Q, killactive\nbind = SUPER, M, exit\n");
     ///
-    /// assert_eq!(expected, rgba);
-    /// assert_eq!(expected, rgb);
-    /// assert_eq!(expected, argb);
+    /// assert_eq!(1, config.remove_headless("bind", |value| value.ends_with("exit")));
+    /// assert_eq!("bind = SUPER, Q, killactive\n", config.to_string());
     /// ```
-    pub fn parse_color(&self, color_str: &str) -> Option<(f32, f32, f32, f32)> {
-        if color_str.starts_with("rgba(") {
-            let rgba = color_str.trim_start_matches("rgba(").trim_end_matches(')');
-            let rgba = u32::from_str_radix(rgba, 16).ok()?;
-            Some((
-                ((rgba >> 24) & 0xFF) as f32 / 255.0,
-                ((rgba >> 16) & 0xFF) as f32 / 255.0,
-                ((rgba >> 8) & 0xFF) as f32 / 255.0,
-                (rgba & 0xFF) as f32 / 255.0,
-            ))
-        } else if color_str.starts_with("rgb(") {
-            let rgb = color_str.trim_start_matches("rgb(").trim_end_matches(')');
-            let rgb = u32::from_str_radix(rgb, 16).ok()?;
-            Some((
-                ((rgb >> 16) & 0xFF) as f32 / 255.0,
-                ((rgb >> 8) & 0xFF) as f32 / 255.0,
-                (rgb & 0xFF) as f32 / 255.0,
-                1.0,
-            ))
-        } else if let Some(stripped) = color_str.strip_prefix("0x") {
-            let argb = u32::from_str_radix(stripped, 16).ok()?;
-            Some((
-                ((argb >> 16) & 0xFF) as f32 / 255.0,
-                ((argb >> 8) & 0xFF) as f32 / 255.0,
-                (argb & 0xFF) as f32 / 255.0,
-                ((argb >> 24) & 0xFF) as f32 / 255.0,
-            ))
-        } else {
-            None
+    pub fn remove_headless(
+        &mut self,
+        key: &str,
+        mut value_predicate: impl FnMut(&str) -> bool,
+    ) -> usize {
+        self.remove_everywhere(|path, node| match node {
+            Node::Assignment(assignment)
+            | Node::Variable(assignment)
+            | Node::Source(assignment) => {
+                path.is_empty() && assignment.key == key && value_predicate(&assignment.value)
+            }
+            _ => false,
+        })
+    }
+
+    fn remove_everywhere(&mut self, mut f: impl FnMut(&CategoryPath, &Node) -> bool) -> usize {
+        let mut removed = self.remove_from(None, &mut f);
+        for index in 0..self.sourced.len() {
+            removed += self.remove_from(Some(index), &mut f);
         }
+        removed
     }
 
-    /// Format a float RGBA color into Hyprland's RGBA
-    pub fn format_color(&self, red: f32, green: f32, blue: f32, alpha: f32) -> String {
-        format!(
-            "rgba({:02x}{:02x}{:02x}{:02x})",
-            (red * 255.0) as u8,
-            (green * 255.0) as u8,
-            (blue * 255.0) as u8,
-            (alpha * 255.0) as u8
-        )
+    fn remove_from(
+        &mut self,
+        file: Option<usize>,
+        f: impl FnMut(&CategoryPath, &Node) -> bool,
+    ) -> usize {
+        let prune = self.remove_empty_categories;
+        let removed = self.document_mut(file).remove_where(prune, f);
+        match file {
+            Some(index) if removed > 0 => self.mark_dirty(index),
+            _ => {}
+        }
+        removed
     }
 
-    fn create_category(&mut self, category: &str, depth: usize, insert_pos: &mut usize) {
-        let part = category.split('.').last().unwrap();
-        let new_section = format!("{}{} {{", "    ".repeat(depth), part);
+    /// Add a [sourced config file](https://wiki.hyprland.org/Configuring/Keywords/#sourcing-multi-file)
+    pub fn add_sourced(&mut self, config: Vec<String>) {
+        let mut file = SourcedFile::new(None, None, None);
+        file.content = Document::parse(&config.join("\n"));
+        self.sourced.push(file);
+    }
 
-        let mut lines_added = 0;
-        if *insert_pos > 0 && !self.content[*insert_pos - 1].trim().is_empty() {
-            self.content.insert(*insert_pos, String::new());
-            *insert_pos += 1;
-            lines_added += 1;
-        }
+    /// Parse a color from Hyprland's config into float RGBA values, in any notation
+    /// [`Color`] accepts
+    #[deprecated(note = "use `color::parse` instead, which tells why a color is invalid")]
+    pub fn parse_color(&self, color_str: &str) -> Option<(f32, f32, f32, f32)> {
+        color::parse(color_str).ok().map(Into::into)
+    }
 
-        self.content.insert(*insert_pos, new_section);
-        *insert_pos += 1;
-        self.content
-            .insert(*insert_pos, format!("{}}}", "    ".repeat(depth)));
-        *insert_pos += 1;
-        self.content.insert(*insert_pos, String::new());
-        *insert_pos += 1;
-
-        self.update_sections(*insert_pos - 3 - lines_added, 3 + lines_added);
-        self.sections.insert(
-            category.to_string(),
-            (*insert_pos - 3 - lines_added, *insert_pos - 2),
-        );
+    /// Format a float RGBA color into Hyprland's RGBA, rounding each channel to the
+    /// nearest 8-bit value
+    #[deprecated(note = "use `color::format` instead")]
+    pub fn format_color(&self, red: f32, green: f32, blue: f32, alpha: f32) -> String {
+        color::format(red, green, blue, alpha)
     }
 
-    fn find_sourced_section(&self, category: &str) -> Option<(usize, (usize, usize))> {
-        for (idx, _) in self.sourced_content.iter().enumerate() {
-            let section_key = format!("{}_{}", category, idx);
-            if let Some(&section) = self.sourced_sections.get(&section_key) {
-                if self.sourced_paths.get(idx).map_or(false, |p| !p.is_empty()) {
-                    return Some((idx, section));
-                }
-            }
-        }
-        None
+    /// Paths of the sourced files edited since they were loaded or saved
+    ///
+    /// Edits only change the config in memory, they reach the disk with
+    /// [`HyprlandConfig::save`].
+    pub fn dirty_files(&self) -> Vec<&Path> {
+        self.sourced
+            .iter()
+            .filter(|file| file.is_dirty())
+            .filter_map(|file| file.path.as_deref())
+            .collect()
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        self.sourced[index].set_dirty(true);
+    }
+
+    fn find_sourced_section(&self, category: &str) -> Option<usize> {
+        self.sourced
+            .iter()
+            .position(|file| file.content.category(category).is_some())
     }
 }
 
+/// State kept while loading a config and the files it sources
+struct Loader<'a, F> {
+    fs: &'a F,
+    scope: Scope,
+    /// Canonical paths of the files being read, to catch cycles
+    chain: Vec<PathBuf>,
+}
+
 /// Automatically parse the whole configuration from str
 pub fn parse_config(config_str: &str) -> HyprlandConfig {
     let mut config = HyprlandConfig::new();
@@ -444,21 +532,35 @@ pub fn parse_config(config_str: &str) -> HyprlandConfig {
     config
 }
 
+/// Parse the whole configuration from str, failing on the first syntax error
+///
+/// Non-fatal problems, such as sourced files that couldn't be read, are left in
+/// [`HyprlandConfig::diagnostics`].
+///
+/// ```rust
+/// use hyprparser::{try_parse_config, ParseErrorKind};
+///
+/// let error = try_parse_config("general {\n    gaps_in = 5\n}\n}\n").unwrap_err();
+///
+/// assert!(matches!(error.kind, ParseErrorKind::UnexpectedClosingBrace));
+/// assert_eq!((4, 1), (error.line, error.column));
+/// ```
+pub fn try_parse_config(config_str: &str) -> Result<HyprlandConfig, ParseError> {
+    let mut config = parse_config(config_str);
+    match config.diagnostics.iter().position(ParseError::is_fatal) {
+        Some(index) => Err(config.diagnostics.remove(index)),
+        None => Ok(config),
+    }
+}
+
 impl fmt::Display for HyprlandConfig {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (i, line) in self.content.iter().enumerate() {
-            if i == self.content.len() - 1 {
-                write!(f, "{}", line)?;
-            } else {
-                writeln!(f, "{}", line)?;
-            }
-        }
-        Ok(())
+        write!(f, "{}", self.content)
     }
 }
 
 impl PartialEq for HyprlandConfig {
     fn eq(&self, other: &Self) -> bool {
-        self.content == other.content
+        self.content.to_string() == other.content.to_string()
     }
 }
@@ -0,0 +1,28 @@
+//! Logging macros forwarding to the [`log`](https://docs.rs/log) facade
+//!
+//! Without the `log` feature the messages are still type-checked but compiled out.
+
+macro_rules! log {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::$level!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => { log!(trace, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { log!(debug, $($arg)+) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => { log!(warn, $($arg)+) };
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => { log!(error, $($arg)+) };
+}
@@ -0,0 +1,128 @@
+//! Line-based three-way merging of file contents
+
+use std::ops::Range;
+
+/// Lines `start..end` of the base replaced with `lines`
+#[derive(Debug)]
+struct Hunk<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+/// Apply both the changes from `base` to `ours` and from `base` to `theirs`
+///
+/// Changes to the same or adjacent lines conflict unless they are identical, in
+/// which case `None` is returned.
+pub(crate) fn merge(base: &str, ours: &str, theirs: &str) -> Option<String> {
+    let base: Vec<_> = base.split_inclusive('\n').collect();
+    let sides = [hunks(&base, ours), hunks(&base, theirs)];
+    let mut hunks: Vec<(usize, &Hunk)> = sides
+        .iter()
+        .enumerate()
+        .flat_map(|(side, hunks)| hunks.iter().map(move |hunk| (side, hunk)))
+        .collect();
+    hunks.sort_by_key(|(_, hunk)| (hunk.start, hunk.end));
+
+    let mut merged = String::new();
+    let mut position = 0;
+    let mut index = 0;
+    while index < hunks.len() {
+        let start = hunks[index].1.start;
+        let mut end = hunks[index].1.end;
+        let mut next = index + 1;
+        while next < hunks.len() && hunks[next].1.start <= end {
+            end = end.max(hunks[next].1.end);
+            next += 1;
+        }
+
+        let group = &hunks[index..next];
+        let original = base[start..end].concat();
+        let ours = apply(&base, start..end, group, 0);
+        let theirs = apply(&base, start..end, group, 1);
+        let version = if ours == original {
+            theirs
+        } else if theirs == original || theirs == ours {
+            ours
+        } else {
+            return None;
+        };
+
+        merged.extend(base[position..start].iter().copied());
+        merged.push_str(&version);
+        position = end;
+        index = next;
+    }
+
+    merged.extend(base[position..].iter().copied());
+    Some(merged)
+}
+
+/// Lines `range` of the base with the hunks of one side applied
+fn apply(base: &[&str], range: Range<usize>, group: &[(usize, &Hunk)], side: usize) -> String {
+    let mut text = String::new();
+    let mut position = range.start;
+    for (_, hunk) in group.iter().filter(|(s, _)| *s == side) {
+        text.extend(base[position..hunk.start].iter().copied());
+        text.extend(hunk.lines.iter().copied());
+        position = hunk.end;
+    }
+    text.extend(base[position..range.end].iter().copied());
+    text
+}
+
+/// Changes turning the lines of `base` into `text`, in order
+fn hunks<'a>(base: &[&str], text: &'a str) -> Vec<Hunk<'a>> {
+    let lines: Vec<_> = text.split_inclusive('\n').collect();
+
+    // Edits are usually small, so only the part between the common prefix and
+    // suffix goes through the quadratic search
+    let prefix = base.iter().zip(&lines).take_while(|(a, b)| a == b).count();
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &base[prefix..base.len() - suffix];
+    let new = &lines[prefix..lines.len() - suffix];
+
+    // Length of the longest common subsequence of every pair of suffixes
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let mut hunk: Option<Hunk> = None;
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            hunks.extend(hunk.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let hunk = hunk.get_or_insert(Hunk {
+            start: prefix + i,
+            end: prefix + i,
+            lines: Vec::new(),
+        });
+        if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            hunk.lines.push(new[j]);
+            j += 1;
+        } else {
+            i += 1;
+            hunk.end = prefix + i;
+        }
+    }
+    hunks.extend(hunk);
+    hunks
+}
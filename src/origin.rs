@@ -0,0 +1,103 @@
+//! Where the nodes of a config come from
+
+use std::fmt;
+use std::path::Path;
+use std::ptr;
+
+use crate::{HyprlandConfig, Node, Position};
+
+/// Where a node of the config comes from, such as line 42 of `looks.conf`, sourced
+/// at line 3 of `hyprland.conf`
+///
+/// Displayed as `looks.conf:42:5, sourced at hyprland.conf:3:1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin<'a> {
+    /// File the node is in, `None` for files parsed from a string
+    pub file: Option<&'a Path>,
+    /// 1-based line, `None` for nodes added after parsing
+    pub line: Option<usize>,
+    /// 1-based column, counted in characters
+    pub column: Option<usize>,
+    /// Where the `source` directives that led to the file are, from the one in the
+    /// main file down, each with an empty chain of its own
+    pub include_chain: Vec<Origin<'a>>,
+}
+
+impl fmt::Display for Origin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_location(f, self)?;
+        for directive in self.include_chain.iter().rev() {
+            write!(f, ", sourced at ")?;
+            write_location(f, directive)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_location(f: &mut fmt::Formatter, origin: &Origin) -> fmt::Result {
+    match origin.file {
+        Some(path) => write!(f, "{}", path.display())?,
+        None => write!(f, "<string>")?,
+    }
+    if let (Some(line), Some(column)) = (origin.line, origin.column) {
+        write!(f, ":{}:{}", line, column)?;
+    }
+    Ok(())
+}
+
+impl HyprlandConfig {
+    /// Where a node of the config comes from, `None` for nodes from elsewhere
+    ///
+    /// ```rust
+    /// use hyprparser::{HyprlandConfig, MemoryFs};
+    ///
+    /// let fs = MemoryFs::new()
+    ///     .with_file("/hypr/hyprland.conf", "$mod = SUPER\nsource = looks.conf\n")
+    ///     .with_file("/hypr/looks.conf", "general {\n    gaps_out = 20\n}\n");
+    ///
+    /// let config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    /// let gaps_out = config.sourced_files()[0].content.iter().nth(1).unwrap();
+    ///
+    /// assert_eq!(
+    ///     "/hypr/looks.conf:2:5, sourced at /hypr/hyprland.conf:2:1",
+    ///     config.origin_of(gaps_out).unwrap().to_string()
+    /// );
+    /// ```
+    pub fn origin_of(&self, node: &Node) -> Option<Origin<'_>> {
+        let file = std::iter::once(None)
+            .chain((0..self.sourced.len()).map(Some))
+            .find(|&file| self.document(file).iter().any(|n| ptr::eq(n, node)))?;
+        Some(self.origin(file, node.trivia().position))
+    }
+
+    /// Origin of a node at `position` in the main file or the sourced file at `file`
+    pub(crate) fn origin(&self, file: Option<usize>, position: Option<Position>) -> Origin<'_> {
+        let mut include_chain = Vec::new();
+        let mut current = file;
+        while let Some(index) = current {
+            let parent = self.sourced[index].parent;
+            if !self.is_unlinked(index) {
+                let position = self
+                    .directive_of(index)
+                    .and_then(|directive| directive.trivia.position);
+                include_chain.push(Origin {
+                    file: self.file_path(parent),
+                    line: position
+                        .map(|position| position.line)
+                        .or(self.sourced[index].line),
+                    column: position.map(|position| position.column),
+                    include_chain: Vec::new(),
+                });
+            }
+            current = parent;
+        }
+        include_chain.reverse();
+
+        Origin {
+            file: self.file_path(file),
+            line: position.map(|position| position.line),
+            column: position.map(|position| position.column),
+            include_chain,
+        }
+    }
+}
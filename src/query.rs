@@ -0,0 +1,272 @@
+//! Reading values back out of a parsed configuration
+
+use std::collections::HashMap;
+
+use crate::{Category, CategoryPath, HyprlandConfig, Node, Origin};
+
+/// An assignment found in the configuration, along with where it comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+    pub origin: Origin<'a>,
+}
+
+/// How an entry or variable differs between two configs, see [`HyprlandConfig::diff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryChange {
+    Added {
+        name: String,
+        value: String,
+    },
+    Removed {
+        name: String,
+        value: String,
+    },
+    Changed {
+        name: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl HyprlandConfig {
+    /// Get the effective value of an option
+    ///
+    /// The path is the category path followed by the key, separated by either `.` or
+    /// `:`, such as `general.gaps_in` or `general:col.active_border`. Headless
+    /// entries are looked up by their key alone. As in Hyprland, the last assignment
+    /// wins, with sourced files taking effect where their `source` directive is.
+    ///
+    /// ```rust
+    /// use hyprparser::parse_config;
+    ///
+    /// let config = parse_config("general {\n    gaps_in = 5\n    gaps_in = 10\n}\n");
+    /// let entry = config.get("general:gaps_in").unwrap();
+    ///
+    /// assert_eq!("10", entry.value);
+    /// assert_eq!(Some(3), entry.origin.line);
+    /// ```
+    pub fn get(&self, path: &str) -> Option<Entry<'_>> {
+        self.get_all(path).pop()
+    }
+
+    /// Every category at a dotted path, including repeated ones such as `device`,
+    /// from the main file and then each sourced file
+    ///
+    /// A single block out of several can be picked with its identifier:
+    ///
+    /// ```rust
+    /// use hyprparser::parse_config;
+    ///
+    /// let config = parse_config(
+    ///     "device {\n    name = mouse\n    sensitivity = -0.5\n}\ndevice[pad] {\n    sensitivity = 1\n}\n",
+    /// );
+    ///
+    /// assert_eq!(2, config.categories("device").len());
+    /// assert_eq!(Some("-0.5"), config.categories("device[mouse]")[0].value("sensitivity"));
+    /// assert_eq!("1", config.get("device[pad].sensitivity").unwrap().value);
+    /// ```
+    pub fn categories(&self, path: &str) -> Vec<&Category> {
+        let mut categories = self.content.categories(path);
+        for file in &self.sourced {
+            categories.extend(file.content.categories(path));
+        }
+        categories
+    }
+
+    /// Get every assignment of an option, such as all `bind` entries, in the order
+    /// Hyprland applies them
+    pub fn get_all(&self, path: &str) -> Vec<Entry<'_>> {
+        let mut entries = Vec::new();
+        self.visit(&mut |category, node, file| match node {
+            Node::Assignment(assignment) if category.matches(path, &assignment.key) => {
+                entries.push(Entry {
+                    key: &assignment.key,
+                    value: &assignment.value,
+                    origin: self.origin(file, assignment.trivia.position),
+                });
+            }
+            _ => {}
+        });
+        entries
+    }
+
+    /// Entries and variables that differ between this config and `other`
+    ///
+    /// Entries are named by their dotted path, with the identifiers of keyed
+    /// categories such as `device[mouse].sensitivity`, and variables by `$name`. A
+    /// name assigned once on both sides is reported as changed, while repeated ones
+    /// such as `bind` are compared as sets of values.
+    ///
+    /// ```rust
+    /// use hyprparser::{parse_config, EntryChange};
+    ///
+    /// let old = parse_config("general {\n    gaps_in = 5\n}\nbind = A\n");
+    /// let new = parse_config("general {\n    gaps_in = 10\n}\nbind = A\nbind = B\n");
+    ///
+    /// assert_eq!(
+    ///     vec![
+    ///         EntryChange::Changed { name: "general.gaps_in".into(), old: "5".into(), new: "10".into() },
+    ///         EntryChange::Added { name: "bind".into(), value: "B".into() },
+    ///     ],
+    ///     old.diff(&new)
+    /// );
+    /// ```
+    pub fn diff(&self, other: &HyprlandConfig) -> Vec<EntryChange> {
+        let (mut names, mut old) = self.values_by_name();
+        let (new_names, mut new) = other.values_by_name();
+        names.extend(new_names.into_iter().filter(|name| !old.contains_key(name)));
+
+        let mut changes = Vec::new();
+        for name in names {
+            let mut removed = old.remove(&name).unwrap_or_default();
+            let mut added = new.remove(&name).unwrap_or_default();
+            if removed == added {
+                continue;
+            }
+            if let ([old], [new]) = (&mut removed[..], &mut added[..]) {
+                changes.push(EntryChange::Changed {
+                    name,
+                    old: std::mem::take(old),
+                    new: std::mem::take(new),
+                });
+                continue;
+            }
+
+            removed.retain(|value| match added.iter().position(|v| v == value) {
+                Some(index) => {
+                    added.remove(index);
+                    false
+                }
+                None => true,
+            });
+            changes.extend(removed.into_iter().map(|value| EntryChange::Removed {
+                name: name.clone(),
+                value,
+            }));
+            changes.extend(added.into_iter().map(|value| EntryChange::Added {
+                name: name.clone(),
+                value,
+            }));
+        }
+        changes
+    }
+
+    /// Values of every entry and variable, grouped by name, along with the names in
+    /// the order they first appear
+    fn values_by_name(&self) -> (Vec<String>, HashMap<String, Vec<String>>) {
+        let mut names = Vec::new();
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        self.visit(&mut |category, node, _| {
+            let (name, value) = match node {
+                Node::Assignment(assignment) => {
+                    (category.qualified_name(&assignment.key), &assignment.value)
+                }
+                Node::Variable(variable) => (variable.key.clone(), &variable.value),
+                _ => return,
+            };
+            if !values.contains_key(&name) {
+                names.push(name.clone());
+            }
+            values.entry(name).or_default().push(value.clone());
+        });
+        (names, values)
+    }
+
+    /// Locate the last node matching `f` that Hyprland applies, as the sourced file
+    /// it is in and how many matching nodes come before it there
+    pub(crate) fn find_effective(
+        &self,
+        f: &mut impl FnMut(&CategoryPath, &Node) -> bool,
+    ) -> Option<(Option<usize>, usize)> {
+        let mut occurrences = HashMap::new();
+        let mut target = None;
+        self.visit(&mut |category, node, file| {
+            if f(category, node) {
+                let occurrence = occurrences.entry(file).or_insert(0);
+                target = Some((file, *occurrence));
+                *occurrence += 1;
+            }
+        });
+        target
+    }
+
+    /// Last node matching `f` that Hyprland applies, along with the index of the
+    /// sourced file it is in
+    pub(crate) fn effective_mut(
+        &mut self,
+        mut f: impl FnMut(&CategoryPath, &Node) -> bool,
+    ) -> Option<(&mut Node, Option<usize>)> {
+        let (file, occurrence) = self.find_effective(&mut f)?;
+        let mut seen = 0;
+        let node = self.document_mut(file).find_node_mut(|path, node| {
+            if !f(path, node) {
+                return false;
+            }
+            seen += 1;
+            seen - 1 == occurrence
+        })?;
+        Some((node, file))
+    }
+
+    /// Call `f` with every assignment and variable definition, the categories it is
+    /// in and the index of the sourced file it is in, in the order Hyprland applies them
+    pub(crate) fn visit<'a>(&'a self, f: &mut impl FnMut(&CategoryPath, &'a Node, Option<usize>)) {
+        self.visit_nodes(&self.content.nodes, &mut CategoryPath::default(), None, f);
+        for index in 0..self.sourced.len() {
+            if self.is_unlinked(index) {
+                self.visit_sourced(index, f);
+            }
+        }
+    }
+
+    fn visit_nodes<'a>(
+        &'a self,
+        nodes: &'a [Node],
+        path: &mut CategoryPath,
+        file: Option<usize>,
+        f: &mut impl FnMut(&CategoryPath, &'a Node, Option<usize>),
+    ) {
+        for node in nodes {
+            match node {
+                Node::Assignment(_) | Node::Variable(_) => f(path, node, file),
+                Node::Category(category) => {
+                    path.push(category);
+                    self.visit_nodes(&category.children, path, file, f);
+                    path.pop();
+                }
+                Node::Source(_) => {
+                    for index in self.loaded_by(node, file) {
+                        self.visit_sourced(index, f);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_sourced<'a>(
+        &'a self,
+        index: usize,
+        f: &mut impl FnMut(&CategoryPath, &'a Node, Option<usize>),
+    ) {
+        if let Some(sourced) = self.sourced.get(index) {
+            self.visit_nodes(
+                &sourced.content.nodes,
+                &mut CategoryPath::default(),
+                Some(index),
+                f,
+            );
+        }
+    }
+}
+
+/// Dotted name of a key inside a category
+pub(crate) fn full_name(category: &str, key: &str) -> String {
+    if category.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", category, key)
+    }
+}
@@ -0,0 +1,188 @@
+//! Loading a config from a file and writing it back
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backup;
+use crate::filesystem::Snapshot;
+use crate::{ConfigFs, Conflict, HyprlandConfig, RealFs, SaveError};
+
+impl HyprlandConfig {
+    /// Load a config file, along with every file it sources
+    ///
+    /// Problems inside the files are reported in [`HyprlandConfig::diagnostics`],
+    /// only failing to read the main file is an error.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::load_with(path, &RealFs)
+    }
+
+    /// Load a config file and the files it sources from `fs`
+    pub fn load_with(path: impl AsRef<Path>, fs: &impl ConfigFs) -> io::Result<Self> {
+        Self::default().load_into(path.as_ref(), fs)
+    }
+
+    /// Load the config again from [`HyprlandConfig::path`], keeping its settings and
+    /// dropping edits that weren't saved
+    pub(crate) fn reloaded_with(&self, fs: &impl ConfigFs) -> io::Result<Self> {
+        let path = self.path.as_deref().ok_or_else(no_path)?;
+        Self {
+            dirs: self.dirs.clone(),
+            remove_empty_categories: self.remove_empty_categories,
+            entry_style: self.entry_style,
+            keep_backups: self.keep_backups,
+            ..Self::default()
+        }
+        .load_into(path, fs)
+    }
+
+    fn load_into(mut self, path: &Path, fs: &impl ConfigFs) -> io::Result<Self> {
+        let config_str = fs.read(path)?;
+        self.path = Some(path.to_path_buf());
+        self.parse_with(&config_str, false, fs);
+        self.loaded = Some(Snapshot::new(config_str));
+        Ok(self)
+    }
+
+    /// Write the config back to [`HyprlandConfig::path`], see [`HyprlandConfig::save_to`]
+    pub fn save(&mut self) -> Result<(), SaveError> {
+        self.save_with(&RealFs)
+    }
+
+    /// Write the config back to [`HyprlandConfig::path`] in `fs`
+    pub fn save_with(&mut self, fs: &impl ConfigFs) -> Result<(), SaveError> {
+        let path = self.path.clone().ok_or_else(no_path)?;
+        self.save_to_with(path, fs)
+    }
+
+    /// Write the main file to `path`, along with every sourced file in
+    /// [`HyprlandConfig::dirty_files`]
+    ///
+    /// The files are written as a unit, see [`RealFs`], after being backed up as
+    /// set by [`HyprlandConfig::keep_backups`]. Saving fails with
+    /// [`SaveError::Conflict`] without writing anything when another program changed
+    /// one of the files since it was loaded or last saved, see
+    /// [`HyprlandConfig::save_merged`].
+    pub fn save_to(&mut self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        self.save_to_with(path, &RealFs)
+    }
+
+    /// Write the main file to `path` in `fs`, along with every sourced file in
+    /// [`HyprlandConfig::dirty_files`]
+    pub fn save_to_with(
+        &mut self,
+        path: impl AsRef<Path>,
+        fs: &impl ConfigFs,
+    ) -> Result<(), SaveError> {
+        self.save_files(path.as_ref(), fs, false)
+    }
+
+    /// Write the config back to [`HyprlandConfig::path`], merging the edits made to
+    /// files that another program changed in the meantime onto their new contents
+    ///
+    /// The config is then loaded again, to pick up the other program's changes.
+    /// Files where both changed the same lines are reported as
+    /// [`SaveError::Conflict`] and nothing is written.
+    pub fn save_merged(&mut self) -> Result<(), SaveError> {
+        self.save_merged_with(&RealFs)
+    }
+
+    /// Like [`HyprlandConfig::save_merged`], with the files in `fs`
+    pub fn save_merged_with(&mut self, fs: &impl ConfigFs) -> Result<(), SaveError> {
+        let path = self.path.clone().ok_or_else(no_path)?;
+        self.save_files(&path, fs, true)
+    }
+
+    fn save_files(
+        &mut self,
+        target: &Path,
+        fs: &impl ConfigFs,
+        merge: bool,
+    ) -> Result<(), SaveError> {
+        let is_main = self.path.as_deref() == Some(target);
+        let mut files = vec![(
+            target.to_path_buf(),
+            self.to_string(),
+            self.loaded.as_ref().filter(|_| is_main),
+        )];
+        for file in &self.sourced {
+            if let (Some(path), true) = (file.path.as_deref(), file.is_dirty()) {
+                if files.iter().all(|(p, _, _)| p != path) {
+                    files.push((
+                        path.to_path_buf(),
+                        file.content.to_string(),
+                        file.loaded.as_ref(),
+                    ));
+                }
+            }
+        }
+
+        let mut writes: Vec<(PathBuf, String, Option<String>)> = Vec::new();
+        let mut conflicts = Vec::new();
+        let mut merged = false;
+        for (path, ours, loaded) in files {
+            let theirs = fs.read(&path).ok();
+            if theirs.as_deref() == Some(ours.as_str()) {
+                continue;
+            }
+            match (loaded, theirs) {
+                (Some(loaded), Some(theirs)) if loaded.is_changed(&theirs) => {
+                    let conflict = Conflict {
+                        path,
+                        base: loaded.text.clone(),
+                        ours,
+                        theirs,
+                    };
+                    match conflict.merge().filter(|_| merge) {
+                        Some(text) => {
+                            debug!("Merged changes to {}", conflict.path.display());
+                            writes.push((conflict.path, text, Some(conflict.theirs)));
+                            merged = true;
+                        }
+                        None => conflicts.push(conflict),
+                    }
+                }
+                (_, theirs) => writes.push((path, ours, theirs)),
+            }
+        }
+
+        if !conflicts.is_empty() {
+            for conflict in &conflicts {
+                warn!(
+                    "{} changed on disk since it was loaded",
+                    conflict.path.display()
+                );
+            }
+            return Err(SaveError::Conflict(conflicts));
+        }
+
+        for (path, _, current) in &writes {
+            if let Some(current) = current {
+                backup::back_up(path, current, self.keep_backups, fs)?;
+            }
+        }
+        let writes: Vec<_> = writes
+            .iter()
+            .map(|(path, text, _)| (path.as_path(), text.clone()))
+            .collect();
+        fs.write_all(&writes)?;
+
+        if merged {
+            *self = self.reloaded_with(fs)?;
+            return Ok(());
+        }
+        if is_main {
+            self.loaded = Some(Snapshot::new(self.to_string()));
+        }
+        for file in &mut self.sourced {
+            if file.path.is_some() && file.is_dirty() {
+                file.loaded = Some(Snapshot::new(file.content.to_string()));
+            }
+            file.set_dirty(false);
+        }
+        Ok(())
+    }
+}
+
+fn no_path() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "the config has no path")
+}
@@ -0,0 +1,632 @@
+//! Files pulled in with `source = path`, which may be glob patterns
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::Snapshot;
+use crate::{Assignment, Document, HyprlandConfig, Node};
+
+/// A file loaded through a `source` directive, or added with
+/// [`HyprlandConfig::add_sourced`] or [`HyprlandConfig::add_source`]
+#[derive(Debug, Clone, Default)]
+pub struct SourcedFile {
+    /// Path the file was read from, `None` for files added from a string
+    pub path: Option<PathBuf>,
+    pub content: Document,
+    /// Index of the sourced file whose `source` directive loaded this one, `None`
+    /// for the main file
+    pub parent: Option<usize>,
+    /// Line of that `source` directive, `None` for files added after parsing
+    pub line: Option<usize>,
+    dirty: bool,
+    /// The file as it was on disk when it was loaded or last saved
+    pub(crate) loaded: Option<Snapshot>,
+}
+
+impl SourcedFile {
+    pub(crate) fn new(path: Option<PathBuf>, parent: Option<usize>, line: Option<usize>) -> Self {
+        Self {
+            path,
+            parent,
+            line,
+            ..Self::default()
+        }
+    }
+
+    /// Whether the file was edited since it was loaded or saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+/// What [`HyprlandConfig::extract_to_source`] moves to the new file
+///
+/// Implemented for category names and for predicates on nodes.
+pub trait Selector {
+    /// Whether a top-level node of the main file is selected
+    fn selects(&mut self, node: &Node) -> bool;
+}
+
+impl Selector for &str {
+    fn selects(&mut self, node: &Node) -> bool {
+        match node {
+            Node::Category(category) => category.matches(self),
+            Node::Assignment(assignment) => assignment
+                .key
+                .strip_prefix(*self)
+                .is_some_and(|rest| rest.starts_with(':')),
+            _ => false,
+        }
+    }
+}
+
+impl<F: FnMut(&Node) -> bool> Selector for F {
+    fn selects(&mut self, node: &Node) -> bool {
+        self(node)
+    }
+}
+
+/// A sourced file, along with the files it sources in turn
+#[derive(Debug, Clone)]
+pub struct Include<'a> {
+    /// Index of the file in [`HyprlandConfig::sourced_files`]
+    pub index: usize,
+    pub file: &'a SourcedFile,
+    pub includes: Vec<Include<'a>>,
+}
+
+/// Directories that `~` and relative `source` paths are resolved against
+///
+/// Defaults to [`Dirs::from_env`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dirs {
+    /// Home directory, standing for `~` and `$HOME`
+    pub home: PathBuf,
+    /// Hyprland's config directory, used for relative sources in a config that
+    /// has no path
+    pub config: PathBuf,
+}
+
+impl Dirs {
+    /// Directories from `$HOME` and `$XDG_CONFIG_HOME`, like Hyprland
+    ///
+    /// The config directory is `$XDG_CONFIG_HOME/hypr`, or `~/.config/hypr` when
+    /// `XDG_CONFIG_HOME` isn't set to an absolute path.
+    pub fn from_env() -> Self {
+        let home = PathBuf::from(env::var_os("HOME").unwrap_or_default());
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| home.join(".config"));
+        Self {
+            config: config_home.join("hypr"),
+            home,
+        }
+    }
+
+    /// Resolve a `source` path written in the file at `base`, or in a config
+    /// without a path when `base` is `None`
+    ///
+    /// ```rust
+    /// use hyprparser::Dirs;
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let dirs = Dirs {
+    ///     home: PathBuf::from("/home/me"),
+    ///     config: PathBuf::from("/home/me/.config/hypr"),
+    /// };
+    /// let base = Path::new("/dotfiles/hypr/hyprland.conf");
+    ///
+    /// assert_eq!(Path::new("/dotfiles/hypr/looks.conf"), dirs.resolve("looks.conf", Some(base)));
+    /// assert_eq!(Path::new("/home/me/.config/hypr/looks.conf"), dirs.resolve("looks.conf", None));
+    /// assert_eq!(Path::new("/home/me/looks.conf"), dirs.resolve("~/looks.conf", Some(base)));
+    /// ```
+    pub fn resolve(&self, path: &str, base: Option<&Path>) -> PathBuf {
+        if path == "~" {
+            return self.home.clone();
+        }
+        if let Some(rest) = path.strip_prefix("~/") {
+            return self.home.join(rest);
+        }
+
+        let dir = match base.and_then(Path::parent) {
+            Some(dir) => dir,
+            None => &self.config,
+        };
+        dir.join(path)
+    }
+}
+
+impl Default for Dirs {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+impl HyprlandConfig {
+    /// Every sourced file, in the order they were loaded
+    ///
+    /// A file comes right after the file that sources it, and before the files it
+    /// sources in turn.
+    pub fn sourced_files(&self) -> &[SourcedFile] {
+        &self.sourced
+    }
+
+    /// Contents of a sourced file, for editing
+    ///
+    /// The file is marked as dirty, to be written by [`HyprlandConfig::save`].
+    pub fn sourced_content_mut(&mut self, index: usize) -> Option<&mut Document> {
+        let file = self.sourced.get_mut(index)?;
+        file.dirty = true;
+        Some(&mut file.content)
+    }
+
+    /// Tree of sourced files, starting with the ones the main file sources
+    ///
+    /// ```rust
+    /// use hyprparser::HyprlandConfig;
+    ///
+    /// let mut config = HyprlandConfig::new();
+    /// config.add_sourced(vec!["general {\n    gaps_in = 5\n}".to_string()]);
+    ///
+    /// let includes = config.includes();
+    /// assert_eq!(1, includes.len());
+    /// assert!(includes[0].includes.is_empty());
+    /// ```
+    pub fn includes(&self) -> Vec<Include<'_>> {
+        self.includes_of(None)
+    }
+
+    fn includes_of(&self, parent: Option<usize>) -> Vec<Include<'_>> {
+        (0..self.sourced.len())
+            .filter(|&index| self.sourced[index].parent == parent)
+            .map(|index| Include {
+                index,
+                file: &self.sourced[index],
+                includes: self.includes_of(Some(index)),
+            })
+            .collect()
+    }
+
+    /// Source a new file from the end of the main file, returning its index
+    ///
+    /// `path` is written in the `source` directive as it is, and resolved like
+    /// Hyprland would to know where to save the file.
+    ///
+    /// ```rust
+    /// use hyprparser::HyprlandConfig;
+    ///
+    /// let mut config = HyprlandConfig::new();
+    /// let index = config.add_source("/tmp/looks.conf", "general {\n    gaps_in = 5\n}\n");
+    ///
+    /// assert_eq!("source = /tmp/looks.conf\n", config.to_string());
+    /// assert_eq!("5", config.get("general.gaps_in").unwrap().value);
+    /// assert!(config.sourced_files()[index].is_dirty());
+    /// ```
+    pub fn add_source(&mut self, path: &str, config_str: &str) -> usize {
+        if let Some(node) = Node::from_line(&format!("source = {}", path), 0) {
+            self.content.push(node);
+        }
+        let resolved = self.dirs.resolve(path, self.path.as_deref());
+        let mut file = SourcedFile::new(Some(resolved), None, None);
+        file.content = Document::parse(config_str);
+        file.dirty = true;
+        self.sourced.push(file);
+        self.sourced.len() - 1
+    }
+
+    /// Stop sourcing a file, returning it
+    ///
+    /// The files it sources are dropped along with it, and its `source` directive is
+    /// removed unless it loaded other files too, as a glob pattern can. Nothing is
+    /// deleted from the disk.
+    pub fn detach_sourced(&mut self, index: usize) -> Option<SourcedFile> {
+        let parent = self.sourced.get(index)?.parent;
+        let directive = self
+            .directive_of(index)
+            .filter(|directive| {
+                let node = Node::Source((*directive).clone());
+                self.loaded_by(&node, parent).len() == 1
+            })
+            .cloned();
+
+        let mut descendants = vec![index];
+        for other in index + 1..self.sourced.len() {
+            if matches!(self.sourced[other].parent, Some(p) if descendants.contains(&p)) {
+                descendants.push(other);
+            }
+        }
+        let mut detached = None;
+        for &other in descendants.iter().rev() {
+            detached = Some(self.remove_sourced(other));
+        }
+
+        if let Some(directive) = directive {
+            self.document_mut(parent)
+                .remove_where(false, |_, node| is_same_directive(node, &directive));
+            if let Some(parent) = parent {
+                self.mark_dirty(parent);
+            }
+        }
+        detached
+    }
+
+    /// Replace the `source` directive of a file with the file's contents
+    ///
+    /// Files it sources are then sourced from the file it was in, which relative
+    /// paths are resolved against the next time the config is loaded. Returns `false`
+    /// when there is no file at `index`, when the file has no `source` directive of
+    /// its own, as files added with [`HyprlandConfig::add_sourced`], or when a glob
+    /// pattern loaded other files with it.
+    ///
+    /// ```rust
+    /// use hyprparser::HyprlandConfig;
+    ///
+    /// let mut config = HyprlandConfig::new();
+    /// config.parse("$a = 1\n", true);
+    ///
+    /// assert!(!config.inline_sourced(0));
+    /// ```
+    pub fn inline_sourced(&mut self, index: usize) -> bool {
+        let Some(directive) = self.directive_of(index).cloned() else {
+            return false;
+        };
+        let parent = self.sourced[index].parent;
+        if self
+            .loaded_by(&Node::Source(directive.clone()), parent)
+            .len()
+            > 1
+        {
+            return false;
+        }
+
+        // Files loaded by each source directive, in the order the directives will
+        // be in once inlined, to move them over to their new lines
+        let mut loads = Vec::new();
+        for node in self.document(parent).iter() {
+            if is_same_directive(node, &directive) {
+                for node in self.sourced[index].content.iter() {
+                    if let Node::Source(_) = node {
+                        loads.push(self.loaded_by(node, Some(index)));
+                    }
+                }
+            } else if let Node::Source(_) = node {
+                loads.push(self.loaded_by(node, parent));
+            }
+        }
+
+        let mut nodes = self.sourced[index].content.nodes.clone();
+        if let Some(last) = nodes.last_mut() {
+            last.terminate();
+        }
+        let document = self.document_mut(parent);
+        replace_node(
+            &mut document.nodes,
+            &|node| is_same_directive(node, &directive),
+            nodes,
+        );
+        *document = Document::parse(&document.to_string());
+
+        let lines: Vec<_> = document
+            .iter()
+            .filter_map(|node| match node {
+                Node::Source(source) => Some(source.trivia.position.map(|p| p.line)),
+                _ => None,
+            })
+            .collect();
+        for (files, line) in loads.into_iter().zip(lines) {
+            for file in files {
+                self.sourced[file].parent = parent;
+                self.sourced[file].line = line;
+            }
+        }
+        self.remove_sourced(index);
+        if let Some(parent) = parent {
+            self.mark_dirty(parent);
+        }
+        true
+    }
+
+    /// The whole config as one document, with every `source` directive replaced by
+    /// the contents of the files it loaded
+    ///
+    /// With `markers`, each inlined file is wrapped in `# >>> path` and `# <<< path`
+    /// comments. Directives that didn't load anything are kept as they are.
+    ///
+    /// ```rust
+    /// use hyprparser::{HyprlandConfig, MemoryFs};
+    ///
+    /// let fs = MemoryFs::new()
+    ///     .with_file("/hypr/hyprland.conf", "source = looks.conf\n$mod = SUPER\n")
+    ///     .with_file("/hypr/looks.conf", "general {\n    gaps_in = 5\n}\n");
+    /// let config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    ///
+    /// assert_eq!(
+    ///     "# >>> /hypr/looks.conf\ngeneral {\n    gaps_in = 5\n}\n# <<< /hypr/looks.conf\n$mod = SUPER\n",
+    ///     config.flatten(true).to_string()
+    /// );
+    /// ```
+    pub fn flatten(&self, markers: bool) -> Document {
+        let mut nodes = self.flatten_nodes(&self.content.nodes, None, markers);
+        for index in 0..self.sourced.len() {
+            if self.sourced[index].parent.is_none() && self.is_unlinked(index) {
+                if let Some(last) = nodes.last_mut() {
+                    last.terminate();
+                }
+                nodes.extend(self.flatten_file(index, markers));
+            }
+        }
+        Document::parse(&Document { nodes }.to_string())
+    }
+
+    fn flatten_nodes(&self, nodes: &[Node], file: Option<usize>, markers: bool) -> Vec<Node> {
+        let mut flat = Vec::new();
+        for node in nodes {
+            match node {
+                Node::Category(category) => {
+                    let mut category = category.clone();
+                    category.children = self.flatten_nodes(&category.children, file, markers);
+                    flat.push(Node::Category(category));
+                }
+                Node::Source(_) => {
+                    let loaded = self.loaded_by(node, file);
+                    if loaded.is_empty() {
+                        flat.push(node.clone());
+                    }
+                    for index in loaded {
+                        flat.extend(self.flatten_file(index, markers));
+                    }
+                }
+                node => flat.push(node.clone()),
+            }
+        }
+        flat
+    }
+
+    fn flatten_file(&self, index: usize, markers: bool) -> Vec<Node> {
+        let file = &self.sourced[index];
+        let name = file
+            .path
+            .as_ref()
+            .map_or("sourced file".into(), |path| path.to_string_lossy());
+
+        let mut nodes = Vec::new();
+        if markers {
+            nodes.extend(Node::from_line(&format!("# >>> {}", name), 0));
+        }
+        nodes.extend(self.flatten_nodes(&file.content.nodes, Some(index), markers));
+        if let Some(last) = nodes.last_mut() {
+            last.terminate();
+        }
+        if markers {
+            nodes.extend(Node::from_line(&format!("# <<< {}", name), 0));
+        }
+        nodes
+    }
+
+    /// Move top-level categories or lines of the main file into a new sourced file,
+    /// returning its index
+    ///
+    /// `selector` is either a category name, which also selects colon-style
+    /// assignments in that category, or a predicate on nodes. The new file is
+    /// sourced where the first selected node was, and `path` is resolved like in
+    /// [`HyprlandConfig::add_source`]. `source` directives are never moved, so that
+    /// the files they load stay where they are. Returns `None` when nothing is
+    /// selected.
+    ///
+    /// ```rust
+    /// use hyprparser::{parse_config, Node};
+    ///
+    /// let mut config = parse_config(
+    ///     "general {\n    gaps_in = 5\n}\nbind = SUPER, Q, killactive\nbind = SUPER, M, exit\n",
+    /// );
+    ///
+    /// config.extract_to_source("general", "/tmp/looks.conf").unwrap();
+    /// config.extract_to_source(
+    ///     |node: &Node| matches!(node, Node::Assignment(a) if a.key == "bind"),
+    ///     "/tmp/binds.conf",
+    /// );
+    ///
+    /// assert_eq!(
+    ///     "source = /tmp/looks.conf\nsource = /tmp/binds.conf\n",
+    ///     config.to_string()
+    /// );
+    /// assert_eq!("5", config.get("general.gaps_in").unwrap().value);
+    /// assert_eq!(2, config.get_all("bind").len());
+    /// ```
+    pub fn extract_to_source(&mut self, mut selector: impl Selector, path: &str) -> Option<usize> {
+        let selected: Vec<_> = (0..self.content.nodes.len())
+            .filter(|&index| {
+                let node = &self.content.nodes[index];
+                !matches!(node, Node::Source(_)) && selector.selects(node)
+            })
+            .collect();
+        let first = *selected.first()?;
+
+        let mut nodes = Vec::new();
+        for &index in selected.iter().rev() {
+            nodes.push(self.content.nodes.remove(index));
+        }
+        nodes.reverse();
+        if let Some(last) = nodes.last_mut() {
+            last.terminate();
+        }
+        if let Some(directive) = Node::from_line(&format!("source = {}", path), 0) {
+            self.content.nodes.insert(first, directive);
+        }
+
+        let resolved = self.dirs.resolve(path, self.path.as_deref());
+        let mut file = SourcedFile::new(Some(resolved), None, None);
+        file.content = Document::parse(&Document { nodes }.to_string());
+        file.dirty = true;
+        self.sourced.push(file);
+        Some(self.sourced.len() - 1)
+    }
+
+    /// Sourced files loaded by `node`, a `source` directive in the file at `file`
+    pub(crate) fn loaded_by(&self, node: &Node, file: Option<usize>) -> Vec<usize> {
+        (0..self.sourced.len())
+            .filter(|&index| {
+                self.sourced[index].parent == file && self.is_directive_of(node, index)
+            })
+            .collect()
+    }
+
+    /// Whether a sourced file has no `source` directive of its own, as files added
+    /// with [`HyprlandConfig::add_sourced`]
+    pub(crate) fn is_unlinked(&self, index: usize) -> bool {
+        self.sourced[index].line.is_none() && self.directive_of(index).is_none()
+    }
+
+    /// Whether `node` is the `source` directive that loaded the sourced file at `index`,
+    /// when it's in that file's parent
+    ///
+    /// Directives added after parsing have no line, and are matched by path instead.
+    fn is_directive_of(&self, node: &Node, index: usize) -> bool {
+        let Node::Source(source) = node else {
+            return false;
+        };
+        let file = &self.sourced[index];
+        match (source.trivia.position, file.line) {
+            (Some(position), Some(line)) => position.line == line,
+            (None, None) => {
+                let base = file.parent.map_or(self.path.as_deref(), |parent| {
+                    self.sourced[parent].path.as_deref()
+                });
+                file.path.as_deref() == Some(&*self.dirs.resolve(&source.value, base))
+            }
+            _ => false,
+        }
+    }
+
+    /// The `source` directive that loaded the sourced file at `index`
+    pub(crate) fn directive_of(&self, index: usize) -> Option<&Assignment> {
+        self.document(self.sourced.get(index)?.parent)
+            .iter()
+            .find(|node| self.is_directive_of(node, index))
+            .and_then(|node| match node {
+                Node::Source(source) => Some(source),
+                _ => None,
+            })
+    }
+
+    /// Remove a sourced file, shifting the indices of the files after it
+    fn remove_sourced(&mut self, index: usize) -> SourcedFile {
+        let file = self.sourced.remove(index);
+        for other in &mut self.sourced {
+            match other.parent {
+                Some(parent) if parent > index => other.parent = Some(parent - 1),
+                Some(parent) if parent == index => other.parent = file.parent,
+                _ => {}
+            }
+        }
+        file
+    }
+}
+
+/// Whether `node` is the same `source` directive as `directive`
+fn is_same_directive(node: &Node, directive: &Assignment) -> bool {
+    matches!(node, Node::Source(source)
+        if source.trivia.position == directive.trivia.position && source.value == directive.value)
+}
+
+/// Replace the first node matching `f`, at any depth, with `replacement`
+fn replace_node(nodes: &mut Vec<Node>, f: &impl Fn(&Node) -> bool, replacement: Vec<Node>) -> bool {
+    let Some(index) = nodes.iter().position(f) else {
+        return nodes.iter_mut().any(|node| match node {
+            Node::Category(category) => {
+                replace_node(&mut category.children, f, replacement.clone())
+            }
+            _ => false,
+        });
+    };
+    nodes.splice(index..=index, replacement);
+    true
+}
+
+/// Whether a path has glob wildcards in it
+pub(crate) fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Match a file name against one component of a glob pattern
+///
+/// As in the shell, wildcards don't match a leading `.` unless the pattern starts
+/// with one.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    (!name.starts_with('.') || pattern.starts_with('.')) && wildcard_match(pattern, name)
+}
+
+/// Match a single file name against a pattern with `*`, `?` and `[...]`
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match_class(&pattern[p..], name[n]),
+            Some(&c) => (c == name[n]).then_some(1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(len), _) => {
+                p += len;
+                n += 1;
+            }
+            (None, Some((star, start))) => {
+                p = star + 1;
+                n = start + 1;
+                backtrack = Some((star, start + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match a character against a `[...]` class at the start of `pattern`, returning
+/// the length of the class when it matches
+///
+/// An unclosed `[` is taken literally.
+fn match_class(pattern: &[char], c: char) -> Option<usize> {
+    let Some(end) = pattern
+        .iter()
+        .skip(2)
+        .position(|&c| c == ']')
+        .map(|i| i + 2)
+    else {
+        return (c == '[').then_some(1);
+    };
+    let class = &pattern[1..end];
+    let (negated, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    (matched != negated).then_some(end + 1)
+}
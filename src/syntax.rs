@@ -0,0 +1,865 @@
+//! Lossless syntax tree of a Hyprland configuration file
+//!
+//! Every byte of the input, including indentation, comments, blank lines and line
+//! terminators, is kept in the tree, so an untouched [`Document`] prints back exactly
+//! the text it was parsed from.
+
+use std::fmt;
+
+use crate::error::{ParseError, ParseErrorKind};
+
+/// Location of the content of a parsed line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    /// Byte offset from the start of the file
+    pub offset: usize,
+}
+
+/// Whitespace and comments surrounding the content of a single line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trivia {
+    /// Whitespace before the content
+    pub indent: String,
+    /// Whitespace and comment after the content
+    pub trailing: String,
+    /// Line terminator, empty on a last line that has none
+    pub newline: String,
+    /// Where the line was parsed from, `None` for lines created by edits
+    pub position: Option<Position>,
+}
+
+impl Trivia {
+    /// Trivia of a freshly written line at the given nesting depth
+    pub fn indented(depth: usize) -> Self {
+        Self {
+            indent: "    ".repeat(depth),
+            trailing: String::new(),
+            newline: "\n".to_string(),
+            position: None,
+        }
+    }
+}
+
+/// A `key = value` line
+///
+/// Also used for variable definitions (`$name = value`) and source directives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub trivia: Trivia,
+    pub key: String,
+    /// Text between the key and the value, including the `=`
+    pub separator: String,
+    pub value: String,
+}
+
+/// A category block such as `general { ... }`
+///
+/// Categories such as `device` can be repeated, each block telling itself apart
+/// with a key in brackets (`device[my-mouse] { ... }`) or a `name` field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    /// Key written in brackets after the name
+    pub key: Option<String>,
+    /// Whitespace between the name and the opening `{`
+    pub spacing: String,
+    /// Trivia of the opening line
+    pub header: Trivia,
+    pub children: Vec<Node>,
+    /// Trivia of the closing `}` line, `None` if the block is never closed
+    pub footer: Option<Trivia>,
+}
+
+impl Category {
+    /// Create an empty category at the given nesting depth
+    pub fn new(name: &str, depth: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            key: None,
+            spacing: " ".to_string(),
+            header: Trivia::indented(depth),
+            children: Vec::new(),
+            footer: Some(Trivia::indented(depth)),
+        }
+    }
+
+    /// First direct subcategory matching `name`, which may be written as
+    /// `name[identifier]`
+    pub fn category(&self, name: &str) -> Option<&Category> {
+        find_category(&self.children, name)
+    }
+
+    /// Identifier telling apart repeated categories: the key in brackets, or
+    /// else the value of the `name` field
+    pub fn identifier(&self) -> Option<&str> {
+        self.key.as_deref().or_else(|| self.value("name"))
+    }
+
+    /// Value of the last direct assignment of `key`
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.assignments()
+            .filter(|assignment| assignment.key == key)
+            .last()
+            .map(|assignment| assignment.value.as_str())
+    }
+
+    /// Whether this category is the one `part` of a category path refers to
+    ///
+    /// `part` is either the plain name, or `name[identifier]` to pick one of
+    /// several repeated categories.
+    pub fn matches(&self, part: &str) -> bool {
+        match part.strip_suffix(']').and_then(|part| part.split_once('[')) {
+            Some((name, identifier)) => self.name == name && self.identifier() == Some(identifier),
+            None => self.name == part,
+        }
+    }
+
+    /// Direct assignments of this category, without those of subcategories
+    pub fn assignments(&self) -> impl Iterator<Item = &Assignment> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Assignment(assignment) => Some(assignment),
+            _ => None,
+        })
+    }
+}
+
+/// A line that couldn't be understood, kept verbatim
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unparsed {
+    pub trivia: Trivia,
+    pub text: String,
+}
+
+/// A single element of the syntax tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// Empty or whitespace-only line, the whitespace is kept in the indent
+    Blank(Trivia),
+    /// Line holding nothing but a comment, kept in the trailing trivia
+    Comment(Trivia),
+    Assignment(Assignment),
+    /// `$name = value`
+    Variable(Assignment),
+    /// `source = path`
+    Source(Assignment),
+    Category(Category),
+    Unparsed(Unparsed),
+}
+
+impl Node {
+    /// Parse a single line of configuration into a node at the given nesting depth
+    ///
+    /// Returns `None` for lines that open or close a category.
+    pub fn from_line(line: &str, depth: usize) -> Option<Self> {
+        let mut node = match parse_line(line, 1, 0) {
+            Line::Node(node) => node,
+            Line::Open(..) | Line::Close(..) => return None,
+        };
+        let trivia = node.trivia_mut();
+        trivia.indent = "    ".repeat(depth);
+        trivia.position = None;
+        if trivia.newline.is_empty() {
+            trivia.newline.push('\n');
+        }
+        Some(node)
+    }
+
+    /// Blank line
+    pub fn blank() -> Self {
+        Node::Blank(Trivia::indented(0))
+    }
+
+    /// Trivia of the first line of the node
+    pub fn trivia(&self) -> &Trivia {
+        match self {
+            Node::Blank(trivia) | Node::Comment(trivia) => trivia,
+            Node::Assignment(assignment)
+            | Node::Variable(assignment)
+            | Node::Source(assignment) => &assignment.trivia,
+            Node::Category(category) => &category.header,
+            Node::Unparsed(unparsed) => &unparsed.trivia,
+        }
+    }
+
+    /// Mutable trivia of the first line of the node
+    pub fn trivia_mut(&mut self) -> &mut Trivia {
+        match self {
+            Node::Blank(trivia) | Node::Comment(trivia) => trivia,
+            Node::Assignment(assignment)
+            | Node::Variable(assignment)
+            | Node::Source(assignment) => &mut assignment.trivia,
+            Node::Category(category) => &mut category.header,
+            Node::Unparsed(unparsed) => &mut unparsed.trivia,
+        }
+    }
+
+    /// Make sure the last line of the node is terminated
+    pub(crate) fn terminate(&mut self) {
+        let trivia = match self {
+            Node::Category(category) => {
+                match (&mut category.footer, category.children.last_mut()) {
+                    (Some(footer), _) => footer,
+                    (None, Some(child)) => return child.terminate(),
+                    (None, None) => &mut category.header,
+                }
+            }
+            node => node.trivia_mut(),
+        };
+        if trivia.newline.is_empty() {
+            trivia.newline.push('\n');
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let trivia = self.trivia();
+        write!(f, "{}", trivia.indent)?;
+        match self {
+            Node::Blank(_) | Node::Comment(_) => {}
+            Node::Assignment(assignment)
+            | Node::Variable(assignment)
+            | Node::Source(assignment) => write!(
+                f,
+                "{}{}{}",
+                assignment.key, assignment.separator, assignment.value
+            )?,
+            Node::Category(category) => {
+                write!(f, "{}", category.name)?;
+                if let Some(key) = &category.key {
+                    write!(f, "[{}]", key)?;
+                }
+                write!(f, "{}{{", category.spacing)?;
+                write!(f, "{}{}", trivia.trailing, trivia.newline)?;
+                for child in &category.children {
+                    write!(f, "{}", child)?;
+                }
+                if let Some(footer) = &category.footer {
+                    write!(
+                        f,
+                        "{}}}{}{}",
+                        footer.indent, footer.trailing, footer.newline
+                    )?;
+                }
+                return Ok(());
+            }
+            Node::Unparsed(unparsed) => write!(f, "{}", unparsed.text)?,
+        }
+        write!(f, "{}{}", trivia.trailing, trivia.newline)
+    }
+}
+
+/// What to do when an added entry's key is already set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddMode {
+    /// Overwrite the last existing assignment of the key
+    #[default]
+    Replace,
+    /// Always write a new line, for keys that can be repeated such as `bind`
+    Append,
+}
+
+/// Root of the syntax tree of one configuration file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub nodes: Vec<Node>,
+}
+
+impl Document {
+    /// Parse a whole configuration file, skipping over malformed lines
+    ///
+    /// Malformed lines are kept in the tree as [`Node::Unparsed`], and unclosed
+    /// categories run until the end of the file.
+    pub fn parse(config_str: &str) -> Self {
+        Self::parse_with_diagnostics(config_str).0
+    }
+
+    /// Parse a whole configuration file, failing on the first malformed line
+    pub fn try_parse(config_str: &str) -> Result<Self, ParseError> {
+        let (document, mut diagnostics) = Self::parse_with_diagnostics(config_str);
+        if diagnostics.is_empty() {
+            Ok(document)
+        } else {
+            Err(diagnostics.remove(0))
+        }
+    }
+
+    /// Parse a whole configuration file, returning every problem found along the way
+    pub fn parse_with_diagnostics(config_str: &str) -> (Self, Vec<ParseError>) {
+        let mut root = Vec::new();
+        let mut stack: Vec<Category> = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut offset = 0;
+
+        for (i, line) in config_str.split_inclusive('\n').enumerate() {
+            let parsed = parse_line(line, i + 1, offset);
+            offset += line.len();
+
+            let node = match parsed {
+                Line::Open(category) => {
+                    stack.push(category);
+                    continue;
+                }
+                Line::Close(footer) => match stack.pop() {
+                    Some(mut category) => {
+                        category.footer = Some(footer);
+                        Node::Category(category)
+                    }
+                    None => {
+                        diagnostics.push(ParseError::at(
+                            ParseErrorKind::UnexpectedClosingBrace,
+                            footer.position,
+                            1,
+                        ));
+                        Node::Unparsed(Unparsed {
+                            trivia: footer,
+                            text: "}".to_string(),
+                        })
+                    }
+                },
+                Line::Node(node) => {
+                    if let Node::Unparsed(unparsed) = &node {
+                        let kind = if unparsed.text.contains('=') {
+                            ParseErrorKind::MissingKey
+                        } else {
+                            ParseErrorKind::MissingEquals
+                        };
+                        diagnostics.push(ParseError::at(
+                            kind,
+                            unparsed.trivia.position,
+                            unparsed.text.len(),
+                        ));
+                    }
+                    node
+                }
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => root.push(node),
+            }
+        }
+
+        while let Some(category) = stack.pop() {
+            diagnostics.push(ParseError::at(
+                ParseErrorKind::UnclosedCategory(category.name.clone()),
+                category.header.position,
+                category.name.len() + category.spacing.len() + 1,
+            ));
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(Node::Category(category)),
+                None => root.push(Node::Category(category)),
+            }
+        }
+
+        diagnostics.sort_by_key(|error| error.span.start);
+        (Self { nodes: root }, diagnostics)
+    }
+
+    /// Find the first category at a dotted path, e.g. `decoration.blur` or
+    /// `device[my-mouse]`
+    pub fn category(&self, path: &str) -> Option<&Category> {
+        let mut parts = split_path(path).into_iter();
+        let mut category = find_category(&self.nodes, parts.next()?)?;
+        for part in parts {
+            category = category.category(part)?;
+        }
+        Some(category)
+    }
+
+    /// Every category at a dotted path, including repeated ones, in file order
+    pub fn categories(&self, path: &str) -> Vec<&Category> {
+        let mut categories = Vec::new();
+        collect_categories(
+            &self.nodes,
+            &mut CategoryPath::default(),
+            path,
+            &mut categories,
+        );
+        categories
+    }
+
+    /// Depth-first iterator over every node of the tree
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            stack: vec![self.nodes.iter()],
+        }
+    }
+
+    /// Append a node at the end of the document
+    pub fn push(&mut self, node: Node) {
+        push_node(&mut self.nodes, node);
+    }
+
+    /// Set `entry` inside the category at the dotted path, creating missing categories
+    ///
+    /// The last assignment of the same key directly inside the category is replaced,
+    /// keeping its indentation and trailing comment.
+    pub fn add_entry(&mut self, category: &str, entry: &str) {
+        self.add_entry_with(category, entry, AddMode::Replace);
+    }
+
+    /// Add `entry` inside the category at the dotted path, creating missing categories
+    ///
+    /// New lines are indented like the last line of their block.
+    pub fn add_entry_with(&mut self, category: &str, entry: &str, mode: AddMode) {
+        let mut nodes = &mut self.nodes;
+        let mut depth = 0;
+        let mut indent = String::new();
+
+        for part in split_path(category) {
+            let index = match nodes
+                .iter()
+                .position(|node| matches!(node, Node::Category(c) if c.matches(part)))
+            {
+                Some(index) => index,
+                None => create_category(nodes, part, depth, &indent),
+            };
+            nodes = match &mut nodes[index] {
+                Node::Category(category) => {
+                    indent = child_indent(&category.children, &category.header.indent);
+                    &mut category.children
+                }
+                _ => unreachable!(),
+            };
+            depth += 1;
+        }
+
+        let Some(mut node) = Node::from_line(entry, depth) else {
+            return;
+        };
+        node.trivia_mut().indent = indent;
+        let existing = match (&node, mode) {
+            (Node::Assignment(new), AddMode::Replace) => {
+                nodes.iter_mut().rev().find_map(|node| match node {
+                    Node::Assignment(assignment) if assignment.key == new.key => Some(assignment),
+                    _ => None,
+                })
+            }
+            _ => None,
+        };
+
+        match (existing, node) {
+            (Some(existing), Node::Assignment(new)) => {
+                existing.key = new.key;
+                existing.separator = new.separator;
+                existing.value = new.value;
+            }
+            (_, node) => push_node(nodes, node),
+        }
+    }
+
+    /// First node, in file order, for which `f` returns `true`
+    ///
+    /// `f` gets the path of the categories holding the node.
+    pub fn find_node_mut(
+        &mut self,
+        mut f: impl FnMut(&CategoryPath, &Node) -> bool,
+    ) -> Option<&mut Node> {
+        find_node(&mut self.nodes, &mut CategoryPath::default(), &mut f)
+    }
+
+    /// Remove every node for which `f` returns `true` and return how many were removed
+    ///
+    /// `f` gets the path of the categories holding the node, empty at the top level.
+    /// With `prune`, categories left with nothing but blank lines by the removal are
+    /// removed as well.
+    pub fn remove_where(
+        &mut self,
+        prune: bool,
+        mut f: impl FnMut(&CategoryPath, &Node) -> bool,
+    ) -> usize {
+        remove_nodes(&mut self.nodes, &mut CategoryPath::default(), prune, &mut f)
+    }
+}
+
+/// Path of the categories leading to a node
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CategoryPath {
+    segments: Vec<(String, Option<String>)>,
+}
+
+impl CategoryPath {
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Whether `pattern` names `key` inside this path, or the innermost category
+    /// itself when `key` is empty
+    ///
+    /// Categories in the pattern are written either by name or as
+    /// `name[identifier]`, separated by `.` or `:`. Separators inside the brackets
+    /// are part of the identifier, as in `device[hid-256c:006d-pen]`.
+    pub fn matches(&self, pattern: &str, key: &str) -> bool {
+        let parts = split_path(pattern);
+        let key = split_path(key);
+        if parts.len() != self.segments.len() + key.len() {
+            return false;
+        }
+
+        let (categories, rest) = parts.split_at(self.segments.len());
+        let categories_match = self.segments.iter().zip(categories).all(
+            |((name, identifier), part)| match identifier {
+                Some(identifier) => part == name || *part == format!("{}[{}]", name, identifier),
+                None => part == name,
+            },
+        );
+        categories_match && rest == key
+    }
+
+    /// Dotted name of `key` inside this path, with the identifiers of keyed
+    /// categories, such as `device[mouse].sensitivity`
+    pub(crate) fn qualified_name(&self, key: &str) -> String {
+        let mut name = String::new();
+        for (segment, identifier) in &self.segments {
+            name.push_str(segment);
+            if let Some(identifier) = identifier {
+                name.push_str(&format!("[{}]", identifier));
+            }
+            name.push('.');
+        }
+        name.push_str(&key.replace(':', "."));
+        name
+    }
+
+    pub(crate) fn push(&mut self, category: &Category) {
+        self.segments.push((
+            category.name.clone(),
+            category.identifier().map(str::to_string),
+        ));
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.segments.pop();
+    }
+}
+
+impl fmt::Display for CategoryPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, _)) in self.segments.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in &self.nodes {
+            write!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+/// Depth-first iterator over the nodes of a [`Document`]
+pub struct Iter<'a> {
+    stack: Vec<std::slice::Iter<'a, Node>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let iter = self.stack.last_mut()?;
+            match iter.next() {
+                Some(node) => {
+                    if let Node::Category(category) = node {
+                        self.stack.push(category.children.iter());
+                    }
+                    return Some(node);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+enum Line {
+    Open(Category),
+    Close(Trivia),
+    Node(Node),
+}
+
+fn parse_line(line: &str, line_number: usize, offset: usize) -> Line {
+    let (line, newline) = if let Some(line) = line.strip_suffix("\r\n") {
+        (line, "\r\n")
+    } else if let Some(line) = line.strip_suffix('\n') {
+        (line, "\n")
+    } else {
+        (line, "")
+    };
+
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    let (content, trailing) = split_comment(body);
+    let trivia = Trivia {
+        indent: indent.to_string(),
+        trailing: trailing.to_string(),
+        newline: newline.to_string(),
+        position: Some(Position {
+            line: line_number,
+            column: indent.chars().count() + 1,
+            offset: offset + indent.len(),
+        }),
+    };
+
+    if content.is_empty() {
+        return Line::Node(if trivia.trailing.is_empty() {
+            Node::Blank(trivia)
+        } else {
+            Node::Comment(trivia)
+        });
+    }
+
+    if content == "}" {
+        return Line::Close(trivia);
+    }
+
+    if let Some(head) = content.strip_suffix('{') {
+        let name = head.trim_end();
+        if !name.is_empty() && !name.contains('=') {
+            let (name, key) = match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+                Some((name, key)) if !name.is_empty() => (name, Some(key.to_string())),
+                _ => (name, None),
+            };
+            return Line::Open(Category {
+                name: name.to_string(),
+                key,
+                spacing: head[head.trim_end().len()..].to_string(),
+                header: trivia,
+                children: Vec::new(),
+                footer: None,
+            });
+        }
+    }
+
+    let Some((key_part, value_part)) = content.split_once('=') else {
+        return Line::Node(Node::Unparsed(Unparsed {
+            trivia,
+            text: content.to_string(),
+        }));
+    };
+
+    let key = key_part.trim_end();
+    if key.is_empty() {
+        return Line::Node(Node::Unparsed(Unparsed {
+            trivia,
+            text: content.to_string(),
+        }));
+    }
+
+    let value = value_part.trim_start();
+    let assignment = Assignment {
+        trivia,
+        key: key.to_string(),
+        separator: format!(
+            "{}={}",
+            &key_part[key.len()..],
+            &value_part[..value_part.len() - value.len()]
+        ),
+        value: value.to_string(),
+    };
+
+    Line::Node(if key.starts_with('$') {
+        Node::Variable(assignment)
+    } else if key == "source" {
+        Node::Source(assignment)
+    } else {
+        Node::Assignment(assignment)
+    })
+}
+
+/// Split a line into its content and the trailing whitespace and comment
+///
+/// `##` is an escaped `#` and doesn't start a comment.
+fn split_comment(body: &str) -> (&str, &str) {
+    let bytes = body.as_bytes();
+    let mut end = body.len();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'#' {
+            if bytes.get(i + 1) == Some(&b'#') {
+                i += 2;
+                continue;
+            }
+            end = i;
+            break;
+        }
+        i += 1;
+    }
+    let content = body[..end].trim_end();
+    (content, &body[content.len()..])
+}
+
+/// Split a path such as `device[hid-256c:006d-pen].sensitivity` at every `.` and
+/// `:` outside brackets
+pub(crate) fn split_path(path: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    if path.is_empty() {
+        return parts;
+    }
+
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in path.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            '.' | ':' if depth == 0 => {
+                parts.push(&path[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&path[start..]);
+    parts
+}
+
+fn find_category<'a>(nodes: &'a [Node], name: &str) -> Option<&'a Category> {
+    nodes.iter().find_map(|node| match node {
+        Node::Category(category) if category.matches(name) => Some(category),
+        _ => None,
+    })
+}
+
+fn collect_categories<'a>(
+    nodes: &'a [Node],
+    path: &mut CategoryPath,
+    pattern: &str,
+    categories: &mut Vec<&'a Category>,
+) {
+    for node in nodes {
+        if let Node::Category(category) = node {
+            path.push(category);
+            if path.matches(pattern, "") {
+                categories.push(category);
+            }
+            collect_categories(&category.children, path, pattern, categories);
+            path.pop();
+        }
+    }
+}
+
+fn find_node<'a>(
+    nodes: &'a mut [Node],
+    path: &mut CategoryPath,
+    f: &mut impl FnMut(&CategoryPath, &Node) -> bool,
+) -> Option<&'a mut Node> {
+    for node in nodes {
+        if f(path, node) {
+            return Some(node);
+        }
+        if let Node::Category(category) = node {
+            path.push(category);
+            let found = find_node(&mut category.children, path, f);
+            path.pop();
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+    None
+}
+
+fn remove_nodes(
+    nodes: &mut Vec<Node>,
+    path: &mut CategoryPath,
+    prune: bool,
+    f: &mut impl FnMut(&CategoryPath, &Node) -> bool,
+) -> usize {
+    let mut removed = 0;
+    let mut i = 0;
+    while i < nodes.len() {
+        if f(path, &nodes[i]) {
+            nodes.remove(i);
+            removed += 1;
+            continue;
+        }
+        if let Node::Category(category) = &mut nodes[i] {
+            path.push(category);
+            let count = remove_nodes(&mut category.children, path, prune, f);
+            path.pop();
+            removed += count;
+            if count > 0
+                && prune
+                && category
+                    .children
+                    .iter()
+                    .all(|child| matches!(child, Node::Blank(_)))
+            {
+                nodes.remove(i);
+                continue;
+            }
+        }
+        i += 1;
+    }
+    removed
+}
+
+fn push_node(nodes: &mut Vec<Node>, node: Node) {
+    if let Some(last) = nodes.last_mut() {
+        last.terminate();
+    }
+    nodes.push(node);
+}
+
+/// Indentation of a new line in a block whose opening line is indented with
+/// `header`: that of its last line, or one level more than the header in an empty
+/// block, a tab when the header uses tabs and 4 spaces otherwise
+fn child_indent(children: &[Node], header: &str) -> String {
+    let last = children
+        .iter()
+        .rev()
+        .find(|node| !matches!(node, Node::Blank(_)));
+    match last {
+        Some(node) => node.trivia().indent.clone(),
+        None if header.contains('\t') => format!("{}\t", header),
+        None => format!("{}    ", header),
+    }
+}
+
+/// Append a new empty category and return its index
+///
+/// `name[identifier]` creates a category with a `name` field set to the identifier.
+fn create_category(nodes: &mut Vec<Node>, name: &str, depth: usize, indent: &str) -> usize {
+    if nodes
+        .last()
+        .is_some_and(|node| !matches!(node, Node::Blank(_)))
+    {
+        push_node(nodes, Node::blank());
+    }
+    let mut category = match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+        Some((name, identifier)) => {
+            let mut category = Category::new(name, depth);
+            let field = format!("name = {}", identifier);
+            category.children.extend(Node::from_line(&field, depth + 1));
+            category
+        }
+        None => Category::new(name, depth),
+    };
+    category.header.indent = indent.to_string();
+    if let Some(footer) = &mut category.footer {
+        footer.indent = indent.to_string();
+    }
+    let child_indent = child_indent(&[], indent);
+    for child in &mut category.children {
+        child.trivia_mut().indent.clone_from(&child_indent);
+    }
+    push_node(nodes, Node::Category(category));
+    let index = nodes.len() - 1;
+    if depth == 0 {
+        nodes.push(Node::blank());
+    }
+    index
+}
@@ -0,0 +1,399 @@
+//! Typed values of entries, parsed from Hyprland's value grammar
+
+use std::fmt;
+
+use crate::color::{Color, Gradient};
+use crate::{query, HyprlandConfig, Node};
+
+/// The value of an entry, such as `true`, `0.5`, `5 10` or `rgba(33ccffee)`
+///
+/// Hyprland reads a value according to the option it is assigned to, which a
+/// config alone doesn't tell. [`Value::parse`] picks the most specific variant,
+/// and the `as_*` methods convert between variants the way Hyprland does, such
+/// as `1` read as a boolean or `0xff33ccff` as a color.
+///
+/// ```rust
+/// use hyprparser::{Gaps, Value};
+///
+/// assert_eq!(Value::Bool(true), Value::parse("yes"));
+/// assert_eq!(Value::Int(255), Value::parse("0xff"));
+/// assert_eq!(Value::Vec2(5.0, 10.0), Value::parse("5 10"));
+/// assert_eq!(Some(Gaps::new(5, 10, 5, 10)), Value::parse("5 10").as_gaps());
+/// assert_eq!(Some(true), Value::parse("1").as_bool());
+/// assert_eq!(Value::String("dwindle".to_string()), Value::parse("dwindle"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `true`, `false`, `yes`, `no`, `on` or `off`
+    Bool(bool),
+    /// Decimal or `0x` hex integer
+    Int(i64),
+    Float(f32),
+    /// Two numbers separated by whitespace, such as `5 10`
+    Vec2(f32, f32),
+    /// Three or four integers, as in CSS
+    Gaps(Gaps),
+    Color(Color),
+    /// Several colors, or colors with an angle
+    Gradient(Gradient),
+    String(String),
+}
+
+/// Gaps on each side, written like CSS margins as 1 to 4 integers
+///
+/// `5` sets every side, `5 10` the top and bottom then the sides, `5 10 15` the
+/// top, the sides then the bottom, and `5 10 15 20` each side clockwise from the
+/// top. Gaps are displayed in the shortest form.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gaps {
+    pub top: i64,
+    pub right: i64,
+    pub bottom: i64,
+    pub left: i64,
+}
+
+impl Gaps {
+    pub const fn new(top: i64, right: i64, bottom: i64, left: i64) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    /// The same gap on every side
+    pub const fn all(gap: i64) -> Self {
+        Self::new(gap, gap, gap, gap)
+    }
+
+    /// Parse 1 to 4 integers separated by whitespace
+    pub fn parse(text: &str) -> Option<Self> {
+        let numbers = text
+            .split_whitespace()
+            .map(|number| number.parse().ok())
+            .collect::<Option<Vec<i64>>>()?;
+        match numbers[..] {
+            [all] => Some(Self::all(all)),
+            [vertical, horizontal] => Some(Self::new(vertical, horizontal, vertical, horizontal)),
+            [top, horizontal, bottom] => Some(Self::new(top, horizontal, bottom, horizontal)),
+            [top, right, bottom, left] => Some(Self::new(top, right, bottom, left)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Gaps {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Self {
+            top,
+            right,
+            bottom,
+            left,
+        } = *self;
+        if right != left {
+            write!(f, "{} {} {} {}", top, right, bottom, left)
+        } else if top != bottom {
+            write!(f, "{} {} {}", top, right, bottom)
+        } else if top != right {
+            write!(f, "{} {}", top, right)
+        } else {
+            write!(f, "{}", top)
+        }
+    }
+}
+
+impl Value {
+    /// Parse a value, falling back to a string for anything else
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        if let Some(bool) = parse_bool(text) {
+            return Self::Bool(bool);
+        }
+        if let Some(int) = parse_int(text) {
+            return Self::Int(int);
+        }
+        if let Some(float) = parse_float(text) {
+            return Self::Float(float);
+        }
+
+        let numbers = text
+            .split_whitespace()
+            .map(parse_float)
+            .collect::<Option<Vec<_>>>();
+        match numbers.as_deref() {
+            Some(&[x, y]) => return Self::Vec2(x, y),
+            Some([_, _, _] | [_, _, _, _]) => {
+                if let Some(gaps) = Gaps::parse(text) {
+                    return Self::Gaps(gaps);
+                }
+            }
+            // Hyprland reads numbers as colors too, but a list of them is rarely one
+            Some(_) => return Self::String(text.to_string()),
+            None => {}
+        }
+
+        if let Ok(color) = text.parse::<Color>() {
+            return Self::Color(color);
+        }
+        match text.parse::<Gradient>() {
+            Ok(gradient) => Self::Gradient(gradient),
+            Err(_) => Self::String(text.to_string()),
+        }
+    }
+
+    /// The value as a boolean, with integers true when they aren't 0
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(bool) => Some(*bool),
+            Self::Int(int) => Some(*int != 0),
+            _ => None,
+        }
+    }
+
+    /// The value as an integer, with booleans as 0 or 1
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(int) => Some(*int),
+            Self::Bool(bool) => Some(i64::from(*bool)),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Self::Float(float) => Some(*float),
+            Self::Int(int) => Some(*int as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec2(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::Vec2(x, y) => Some((*x, *y)),
+            _ => None,
+        }
+    }
+
+    /// The value as gaps, from 1 to 4 integers
+    pub fn as_gaps(&self) -> Option<Gaps> {
+        match self {
+            Self::Gaps(gaps) => Some(*gaps),
+            Self::Int(_) | Self::Vec2(..) => Gaps::parse(&self.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The value as a color, with integers read as `0xAARRGGBB`
+    pub fn as_color(&self) -> Option<Color> {
+        match self {
+            Self::Color(color) => Some(*color),
+            Self::Int(_) => self.to_string().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a gradient, with a color being a gradient of one stop
+    pub fn as_gradient(&self) -> Option<Gradient> {
+        match self {
+            Self::Gradient(gradient) => Some(gradient.clone()),
+            _ => self.as_color().map(Gradient::from),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Write the value in the notation of `existing`, the text it replaces, when
+    /// the value doesn't have one of its own
+    fn to_string_like(&self, existing: &str) -> String {
+        match self {
+            Self::Bool(bool) => {
+                let (yes, no) = match existing.trim().to_ascii_lowercase().as_str() {
+                    "yes" | "no" => ("yes", "no"),
+                    "on" | "off" => ("on", "off"),
+                    "1" | "0" => ("1", "0"),
+                    _ => ("true", "false"),
+                };
+                if *bool { yes } else { no }.to_string()
+            }
+            Self::Int(int) if *int >= 0 => match existing.trim().strip_prefix("0x") {
+                Some(hex) if hex.chars().any(|c| c.is_ascii_uppercase()) => {
+                    format!("0x{:0width$X}", int, width = hex.len())
+                }
+                Some(hex) => format!("0x{:0width$x}", int, width = hex.len()),
+                None => int.to_string(),
+            },
+            Self::Color(color) if color.style.is_none() => match existing.parse::<Color>() {
+                Ok(old) => Color {
+                    style: old.style,
+                    ..*color
+                }
+                .to_string(),
+                Err(_) => color.to_string(),
+            },
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool(bool) => write!(f, "{}", bool),
+            Self::Int(int) => write!(f, "{}", int),
+            Self::Float(float) => write!(f, "{}", float),
+            Self::Vec2(x, y) => write!(f, "{} {}", x, y),
+            Self::Gaps(gaps) => write!(f, "{}", gaps),
+            Self::Color(color) => write!(f, "{}", color),
+            Self::Gradient(gradient) => write!(f, "{}", gradient),
+            Self::String(string) => write!(f, "{}", string),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Self::Bool(bool)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(int: i32) -> Self {
+        Self::Int(int.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(int: i64) -> Self {
+        Self::Int(int)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(float: f32) -> Self {
+        Self::Float(float)
+    }
+}
+
+impl From<(f32, f32)> for Value {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self::Vec2(x, y)
+    }
+}
+
+impl From<Gaps> for Value {
+    fn from(gaps: Gaps) -> Self {
+        Self::Gaps(gaps)
+    }
+}
+
+impl From<Color> for Value {
+    fn from(color: Color) -> Self {
+        Self::Color(color)
+    }
+}
+
+impl From<Gradient> for Value {
+    fn from(gradient: Gradient) -> Self {
+        Self::Gradient(gradient)
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Self::String(string)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Self::String(string.to_string())
+    }
+}
+
+impl HyprlandConfig {
+    /// Effective value of an option, with the variables defined before it expanded,
+    /// see [`HyprlandConfig::get`]
+    ///
+    /// ```rust
+    /// use hyprparser::{parse_config, Value};
+    ///
+    /// let config = parse_config("$gaps = 5\ngeneral {\n    gaps_in = $gaps\n}\n$gaps = 10\n");
+    ///
+    /// assert_eq!(Some(Value::Int(5)), config.get_value("general.gaps_in"));
+    /// ```
+    pub fn get_value(&self, path: &str) -> Option<Value> {
+        Some(Value::parse(&self.expanded_value(path)?))
+    }
+
+    /// Set `key` in `category` to `value`, replacing the effective assignment
+    /// wherever it is, like [`HyprlandConfig::add_entry`]
+    ///
+    /// Booleans, integers, and colors without a [`Color::style`], are written in the
+    /// notation of the value they replace, with its variables expanded. Headless entries have
+    /// an empty category.
+    ///
+    /// ```rust
+    /// use hyprparser::{parse_config, Color};
+    ///
+    /// let mut config = parse_config("general {\n    col.active_border = 0xff33ccff\n}\n");
+    /// config.set("general", "col.active_border", Color::new(1.0, 0.5, 0.0, 1.0));
+    /// config.set("decoration.blur", "enabled", true);
+    /// config.set("", "exec-once", "waybar");
+    ///
+    /// assert_eq!("0xffff8000", config.get("general.col.active_border").unwrap().value);
+    /// assert_eq!("true", config.get("decoration.blur.enabled").unwrap().value);
+    /// assert_eq!("waybar", config.get("exec-once").unwrap().value);
+    /// ```
+    pub fn set(&mut self, category: &str, key: &str, value: impl Into<Value>) {
+        let value = value.into();
+        let text = match self.expanded_value(&query::full_name(category, key)) {
+            Some(existing) => value.to_string_like(&existing),
+            None => value.to_string(),
+        };
+        let entry = format!("{} = {}", key, text);
+
+        if !category.is_empty() {
+            self.add_entry(category, &entry);
+            return;
+        }
+        match Node::from_line(&entry, 0) {
+            Some(Node::Assignment(new)) if self.replace_effective(category, &new) => {}
+            Some(node) => self.content.push(node),
+            None => {}
+        }
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse a decimal integer, or a hex one starting with `0x`
+fn parse_int(text: &str) -> Option<i64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parse a finite float, leaving out words such as `inf` and `nan` as well as
+/// numbers too large for an `f32`
+fn parse_float(text: &str) -> Option<f32> {
+    if !text
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+    {
+        return None;
+    }
+    text.parse().ok().filter(|float: &f32| float.is_finite())
+}
@@ -0,0 +1,189 @@
+//! Hyprland's `$name = value` variables
+
+use crate::{CategoryPath, HyprlandConfig, Node, Origin};
+
+/// A variable definition, such as `$terminal = kitty`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable<'a> {
+    /// Name without the leading `$`
+    pub name: &'a str,
+    /// Value as written in the config
+    pub raw: &'a str,
+    /// Value with the variables defined before it expanded
+    pub value: String,
+    pub origin: Origin<'a>,
+}
+
+impl HyprlandConfig {
+    /// Every variable definition, in the order Hyprland reads them
+    ///
+    /// A variable defined more than once shows up once per definition. Each value is
+    /// expanded with the variables defined before it, as Hyprland does.
+    ///
+    /// ```rust
+    /// use hyprparser::parse_config;
+    ///
+    /// let config = parse_config("$a = 1\n$ab = $a2\n$a = 3\n");
+    /// let values: Vec<_> = config.variables().into_iter().map(|v| v.value).collect();
+    ///
+    /// assert_eq!(vec!["1", "12", "3"], values);
+    /// ```
+    pub fn variables(&self) -> Vec<Variable<'_>> {
+        let mut scope = Scope::default();
+        let mut variables = Vec::new();
+        self.visit(&mut |_, node, file| {
+            if let Node::Variable(variable) = node {
+                variables.push(Variable {
+                    name: &variable.key[1..],
+                    raw: &variable.value,
+                    value: scope
+                        .define(&variable.key[1..], &variable.value)
+                        .to_string(),
+                    origin: self.origin(file, variable.trivia.position),
+                });
+            }
+        });
+        variables
+    }
+
+    /// Effective definition of a variable, the last one Hyprland reads
+    ///
+    /// The name may be given with or without the leading `$`.
+    pub fn get_variable(&self, name: &str) -> Option<Variable<'_>> {
+        let name = name.strip_prefix('$').unwrap_or(name);
+        self.variables().into_iter().rev().find(|v| v.name == name)
+    }
+
+    /// Set the value of a variable
+    ///
+    /// The effective definition is updated where it is. A new variable is defined
+    /// at the top of the main file, before anything that could use it.
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        let name = name.strip_prefix('$').unwrap_or(name);
+        let key = format!("${}", name);
+
+        let is_definition = |_: &CategoryPath, node: &Node| matches!(node, Node::Variable(variable) if variable.key == key);
+
+        match self.effective_mut(is_definition) {
+            Some((Node::Variable(variable), file)) => {
+                variable.value = value.to_string();
+                if let Some(index) = file {
+                    self.mark_dirty(index);
+                }
+            }
+            _ => {
+                let Some(node) = Node::from_line(&format!("{} = {}", key, value), 0) else {
+                    return;
+                };
+                let index = self
+                    .content
+                    .nodes
+                    .iter()
+                    .position(|node| !matches!(node, Node::Blank(_) | Node::Comment(_)))
+                    .unwrap_or(self.content.nodes.len());
+                self.content.nodes.insert(index, node);
+            }
+        }
+    }
+
+    /// Effective value of an option, expanded with the variables defined before it
+    /// as Hyprland does, see [`HyprlandConfig::get`]
+    pub(crate) fn expanded_value(&self, path: &str) -> Option<String> {
+        let mut scope = Scope::default();
+        let mut value = None;
+        self.visit(&mut |category, node, _| match node {
+            Node::Variable(variable) => {
+                scope.define(&variable.key[1..], &variable.value);
+            }
+            Node::Assignment(assignment) if category.matches(path, &assignment.key) => {
+                value = Some(scope.expand(&assignment.value));
+            }
+            _ => {}
+        });
+        value
+    }
+
+    /// Expand every `$variable` in `text` with the variables' effective values
+    ///
+    /// ```rust
+    /// use hyprparser::parse_config;
+    ///
+    /// let config = parse_config("$mod = SUPER\n$modShift = SUPER SHIFT\n");
+    ///
+    /// assert_eq!("SUPER SHIFT, Q", config.expand("$modShift, Q"));
+    /// assert_eq!("SUPER, $unknown", config.expand("$mod, $unknown"));
+    /// ```
+    pub fn expand(&self, text: &str) -> String {
+        let mut scope = Scope::default();
+        for variable in self.variables() {
+            scope.set(variable.name, variable.value);
+        }
+        scope.expand(text)
+    }
+}
+
+/// Variables defined so far while reading a config from top to bottom
+#[derive(Debug, Default)]
+pub(crate) struct Scope {
+    variables: Vec<(String, String)>,
+}
+
+impl Scope {
+    /// Define a variable, expanding its value with the variables defined so far
+    pub(crate) fn define(&mut self, name: &str, raw: &str) -> &str {
+        let value = self.expand(raw);
+        self.set(name, value)
+    }
+
+    fn set(&mut self, name: &str, value: String) -> &str {
+        let index = match self.variables.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.variables[index].1 = value;
+                index
+            }
+            None => {
+                self.variables.push((name.to_string(), value));
+                self.variables.len() - 1
+            }
+        };
+        &self.variables[index].1
+    }
+
+    /// Replace every `$name` in `text` with its value, always picking the longest
+    /// defined name at each `$`
+    ///
+    /// Values are substituted as they are, without being expanded again.
+    pub(crate) fn expand(&self, text: &str) -> String {
+        self.expand_with_home(text, "")
+    }
+
+    /// Like [`Scope::expand`], with `$HOME` standing for `home` unless it is defined
+    pub(crate) fn expand_with_home(&self, text: &str, home: &str) -> String {
+        let home = (!home.is_empty() && !self.variables.iter().any(|(name, _)| name == "HOME"))
+            .then_some(("HOME", home));
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(index) = rest.find('$') {
+            expanded.push_str(&rest[..index]);
+            rest = &rest[index + 1..];
+            let longest = self
+                .variables
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .chain(home)
+                .filter(|(name, _)| !name.is_empty() && rest.starts_with(name))
+                .max_by_key(|(name, _)| name.len());
+            match longest {
+                Some((name, value)) => {
+                    expanded.push_str(value);
+                    rest = &rest[name.len()..];
+                }
+                None => expanded.push('$'),
+            }
+        }
+
+        expanded.push_str(rest);
+        expanded
+    }
+}
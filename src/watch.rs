@@ -0,0 +1,247 @@
+//! Reloading a config when its files change on disk, behind the `watch` feature
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::source::is_glob;
+use crate::{EntryChange, HyprlandConfig, RealFs};
+
+/// A reload of a watched config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEvent {
+    /// Files whose contents changed, were added or went away
+    pub files: Vec<PathBuf>,
+    /// Entries and variables that changed, see [`HyprlandConfig::diff`]
+    pub changes: Vec<EntryChange>,
+}
+
+/// Watches the main file of a config and every file it sources, reloading the
+/// config when they change
+///
+/// The directories the files are in are watched rather than the files themselves,
+/// so that editors saving through a temporary file are noticed too, as are sourced
+/// files and glob matches that appear later. Reloading throws away edits that
+/// weren't saved.
+///
+/// ```rust,no_run
+/// use hyprparser::{ConfigWatcher, Dirs, HyprlandConfig};
+///
+/// let config = HyprlandConfig::load(Dirs::from_env().config.join("hyprland.conf")).unwrap();
+/// let mut watcher = ConfigWatcher::new(config).unwrap();
+///
+/// loop {
+///     let event = watcher.recv().unwrap();
+///     println!("{:?}", event.changes);
+///     println!("gaps_in = {:?}", watcher.config().get("general.gaps_in"));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    config: HyprlandConfig,
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    watched: Vec<PathBuf>,
+    /// How long the files have to stay untouched before reloading, as saving can
+    /// take several writes
+    pub debounce: Duration,
+}
+
+impl ConfigWatcher {
+    /// Start watching a config loaded from a file
+    pub fn new(config: HyprlandConfig) -> io::Result<Self> {
+        if config.path.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the config has no path",
+            ));
+        }
+
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+        let mut watcher = Self {
+            config,
+            watcher,
+            events,
+            watched: Vec::new(),
+            debounce: Duration::from_millis(50),
+        };
+        watcher.update_watches()?;
+        Ok(watcher)
+    }
+
+    /// The config as of the last reload
+    pub fn config(&self) -> &HyprlandConfig {
+        &self.config
+    }
+
+    pub fn into_config(self) -> HyprlandConfig {
+        self.config
+    }
+
+    /// Wait until the config changes
+    pub fn recv(&mut self) -> io::Result<ConfigEvent> {
+        loop {
+            if let Some(event) = self.recv_timeout(Duration::from_secs(3600))? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Wait until the config changes, for at most `timeout`
+    pub fn recv_timeout(&mut self, timeout: Duration) -> io::Result<Option<ConfigEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(remaining) {
+                Ok(event) => {
+                    if !is_change(&event.map_err(io::Error::other)?) {
+                        continue;
+                    }
+                    self.settle()?;
+                    let event = self.reload();
+                    // Directories a missing source would be in may have appeared
+                    self.update_watches()?;
+                    if event.is_some() {
+                        return Ok(event);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("the file watcher stopped"))
+                }
+            }
+        }
+    }
+
+    /// Reload the config if it changed, without waiting
+    pub fn try_recv(&mut self) -> io::Result<Option<ConfigEvent>> {
+        self.recv_timeout(Duration::ZERO)
+    }
+
+    /// Wait for the files to stay untouched for [`ConfigWatcher::debounce`]
+    fn settle(&mut self) -> io::Result<()> {
+        loop {
+            match self.events.recv_timeout(self.debounce) {
+                Ok(event) => {
+                    event.map_err(io::Error::other)?;
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("the file watcher stopped"))
+                }
+            }
+        }
+    }
+
+    /// Load the config again, returning what changed if anything did
+    ///
+    /// A main file that can't be read, as while an editor replaces it, counts as
+    /// unchanged.
+    fn reload(&mut self) -> Option<ConfigEvent> {
+        let old = &self.config;
+        let new = old.reloaded_with(&RealFs).ok()?;
+
+        let old_files = files(old);
+        let new_files = files(&new);
+        let mut files: Vec<_> = old_files
+            .iter()
+            .filter(|file| !new_files.contains(file))
+            .chain(new_files.iter().filter(|file| !old_files.contains(file)))
+            .map(|(path, _)| path.clone())
+            .collect();
+        files.sort();
+        files.dedup();
+        if files.is_empty() {
+            return None;
+        }
+
+        debug!("Reloaded the config after changes to {:?}", files);
+        let changes = old.diff(&new);
+        self.config = new;
+        Some(ConfigEvent { files, changes })
+    }
+
+    /// Watch the directories of the files the config is made of, and of the paths
+    /// and glob patterns it sources, to notice files appearing there
+    fn update_watches(&mut self) -> io::Result<()> {
+        let mut dirs: Vec<PathBuf> = files(&self.config)
+            .into_iter()
+            .map(|(path, _)| path)
+            .chain(self.config.source_paths.iter().cloned())
+            .filter_map(|path| watched_dir(&path))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        for dir in &self.watched {
+            if !dirs.contains(dir) {
+                let _ = self.watcher.unwatch(dir);
+            }
+        }
+        for dir in &dirs {
+            if !self.watched.contains(dir) {
+                trace!("Watching {}", dir.display());
+                self.watcher
+                    .watch(dir, RecursiveMode::NonRecursive)
+                    .map_err(io::Error::other)?;
+            }
+        }
+        self.watched = dirs;
+        Ok(())
+    }
+}
+
+impl HyprlandConfig {
+    /// Start watching the files this config was loaded from, see [`ConfigWatcher`]
+    pub fn watch(self) -> io::Result<ConfigWatcher> {
+        ConfigWatcher::new(self)
+    }
+}
+
+/// Every file of a config with a path, along with its contents
+fn files(config: &HyprlandConfig) -> Vec<(PathBuf, String)> {
+    config
+        .path
+        .iter()
+        .map(|path| (path.clone(), config.content.to_string()))
+        .chain(
+            config
+                .sourced_files()
+                .iter()
+                .filter_map(|file| Some((file.path.clone()?, file.content.to_string()))),
+        )
+        .collect()
+}
+
+/// Directory to watch for a file or glob pattern, the closest one above it that
+/// exists and has no glob character
+fn watched_dir(path: &Path) -> Option<PathBuf> {
+    let mut dir = path.parent()?;
+    loop {
+        if dir.as_os_str().is_empty() {
+            return Path::new(".").canonicalize().ok();
+        }
+        if !is_glob(&dir.to_string_lossy()) {
+            if let Ok(dir) = dir.canonicalize() {
+                return Some(dir);
+            }
+        }
+        dir = dir.parent()?;
+    }
+}
+
+/// Whether an event may have changed a file, rather than just read it
+fn is_change(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Modify(_)
+            | EventKind::Remove(_)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+    )
+}
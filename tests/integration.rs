@@ -41,29 +41,1379 @@ fn entry_adding() {
 
 #[test]
 fn color_parsing() {
-    let config = HyprlandConfig::new();
+    use hyprparser::color;
 
-    let expected = Some((0.11764706, 0.27450982, 0.19607843, 1.0));
+    let expected = Ok((0.11764706, 0.27450982, 0.19607843, 1.0));
 
-    let rgba_parsed = config.parse_color("rgba(1E4632FF)");
-    let rgb_parsed = config.parse_color("rgb(1E4632)");
-    let argb_parsed = config.parse_color("0xFF1E4632");
+    let rgba_parsed = color::parse("rgba(1E4632FF)").map(Into::into);
+    let rgb_parsed = color::parse("rgb(1E4632)").map(Into::into);
+    let argb_parsed = color::parse("0xFF1E4632").map(Into::into);
 
     assert_eq!(expected, rgba_parsed);
     assert_eq!(expected, rgb_parsed);
     assert_eq!(expected, argb_parsed);
+
+    #[allow(deprecated)]
+    {
+        let config = HyprlandConfig::new();
+        assert_eq!(expected.ok(), config.parse_color("rgba(1E4632FF)"));
+        assert_eq!(
+            "rgba(1e4632ff)",
+            config.format_color(0.11764706, 0.27450982, 0.19607843, 1.0)
+        );
+    }
 }
 
 #[test]
 fn file_sourcing() {
-    let config_parsed = parse_config(&fs::read_to_string(TEST_CONFIG_FILENAME_1).unwrap());
+    let mut config_parsed = HyprlandConfig::new();
+    config_parsed.path = Some(TEST_CONFIG_FILENAME_1.into());
+    config_parsed.parse(&fs::read_to_string(TEST_CONFIG_FILENAME_1).unwrap(), false);
     let mut config = HyprlandConfig::new();
 
-    config.add_entry_headless(
-        "source",
-        &format!("{}/tests/test_config_2.conf", env!("CARGO_MANIFEST_DIR")),
-    );
+    config.add_entry_headless("source", "./test_config_2.conf");
     config.add_sourced(vec![fs::read_to_string(TEST_CONFIG_FILENAME_2).unwrap()]);
 
-    assert_eq!(config_parsed, config)
+    assert_eq!(config_parsed, config);
+    assert!(config_parsed.diagnostics.is_empty());
+    assert_eq!(
+        config.sourced_files()[0].content.to_string(),
+        config_parsed.sourced_files()[0].content.to_string()
+    );
+}
+
+#[test]
+fn lossless_round_trip() {
+    let config_str = fs::read_to_string(TEST_CONFIG_FILENAME_0).unwrap();
+    assert_eq!(config_str, parse_config(&config_str).to_string());
+
+    let config_str = "# comment\r\n\tgeneral{  # open\r\n  gaps_in=5 # five\r\n\n   }\n}\n$a = b ## not a comment\ninput {\n    sensitivity = 0";
+    assert_eq!(config_str, parse_config(config_str).to_string());
+}
+
+#[test]
+fn entry_editing_keeps_trivia() {
+    let mut config = parse_config("general {\n  # gaps\n  gaps_in = 5 # inner\n}\n");
+
+    config.add_entry("general", "gaps_in = 10");
+    config.add_entry("general", "gaps_out = 20");
+
+    assert_eq!(
+        "general {\n  # gaps\n  gaps_in = 10 # inner\n  gaps_out = 20\n}\n",
+        config.to_string()
+    );
+
+    // New lines follow the indentation of their block, tabs included
+    let mut config = parse_config("general {\n\tgaps_in = 5\n\tsnap {\n\t}\n}\n");
+    config.add_entry("general", "gaps_out = 5");
+    config.add_entry("general.snap", "enabled = true");
+    config.add_entry("general.layout", "name = dwindle");
+    assert_eq!(
+        "general {\n\tgaps_in = 5\n\tsnap {\n\t\tenabled = true\n\t}\n\tgaps_out = 5\n\n\tlayout {\n\t\tname = dwindle\n\t}\n}\n",
+        config.to_string()
+    );
+}
+
+#[test]
+fn parse_errors() {
+    use hyprparser::{try_parse_config, ParseErrorKind};
+
+    let error = try_parse_config("general {\n    gaps_in 5\n}\n").unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::MissingEquals));
+    assert_eq!((2, 5, 14..23), (error.line, error.column, error.span));
+
+    let error = try_parse_config("input {\n    touchpad {\n}\n").unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::UnclosedCategory(ref name) if name == "input"));
+    assert_eq!((1, 1, 0..7), (error.line, error.column, error.span));
+
+    let config = try_parse_config("source = /nonexistent/hyprparser.conf\n").unwrap();
+    assert_eq!(1, config.diagnostics.len());
+    assert!(!config.diagnostics[0].is_fatal());
+
+    let config = parse_config("}\n= 5\n");
+    assert_eq!(2, config.diagnostics.len());
+    assert_eq!("}\n= 5\n", config.to_string());
+}
+
+#[test]
+fn entry_reading() {
+    let dir = std::env::temp_dir().join("hyprparser_entry_reading");
+    fs::create_dir_all(&dir).unwrap();
+    let sourced_path = dir.join("looks.conf");
+    fs::write(
+        &sourced_path,
+        "general {\n    gaps_in = 2\n    gaps_out = 9\n}\n",
+    )
+    .unwrap();
+
+    let config = parse_config(&format!(
+        "source = {}\ngeneral {{\n    gaps_in = 5\n    col.active_border = rgb(BDBDBD)\n}}\nbind = a\nbind = b\n",
+        sourced_path.display()
+    ));
+
+    let gaps_in = config.get("general.gaps_in").unwrap();
+    assert_eq!(
+        ("5", None, Some(3)),
+        (gaps_in.value, gaps_in.origin.file, gaps_in.origin.line)
+    );
+
+    let gaps_out = config.get("general:gaps_out").unwrap();
+    assert_eq!("9", gaps_out.value);
+    assert_eq!(Some(sourced_path.as_path()), gaps_out.origin.file);
+
+    assert_eq!(
+        "rgb(BDBDBD)",
+        config.get("general.col.active_border").unwrap().value
+    );
+    assert_eq!(2, config.get_all("general.gaps_in").len());
+    assert_eq!(
+        vec!["a", "b"],
+        config
+            .get_all("bind")
+            .iter()
+            .map(|e| e.value)
+            .collect::<Vec<_>>()
+    );
+    assert!(config.get("general.gaps").is_none());
+}
+
+#[test]
+fn entry_removing() {
+    let mut config = parse_config(
+        "general {\n    gaps_in = 5\n    gaps_in = 10\n    gaps_out = 20\n}\n\ndecoration {\n    blur {\n        size = 3\n    }\n}\nbind = SUPER, Q, killactive\n",
+    );
+    config.add_sourced(vec!["general {\n    gaps_out = 2\n}".to_string()]);
+
+    assert!(config.remove_entry("general", "gaps_in"));
+    assert_eq!("5", config.get("general.gaps_in").unwrap().value);
+    assert!(!config.remove_entry("general", "gaps"));
+
+    assert_eq!(2, config.remove_all("general", "gaps_out"));
+    assert!(config.get("general.gaps_out").is_none());
+
+    config.remove_empty_categories = true;
+    assert_eq!(1, config.remove_all("decoration.blur", "size"));
+    assert_eq!(1, config.remove_headless("bind", |_| true));
+
+    assert_eq!(2, config.remove_category("general"));
+    assert_eq!("\n", config.to_string());
+}
+
+#[test]
+fn entry_adding_exact_keys() {
+    let mut config = parse_config(
+        "general {\n    gaps_in_something = 1\n    col.active_border = rgb(000000)\n    nested {\n        gaps_in = 2\n    }\n}\n",
+    );
+
+    config.add_entry("general", "gaps_in = 5");
+    config.add_entry("general", "col.active = rgb(ffffff)");
+    config.add_entry_with("general", "gaps_in = 6", hyprparser::AddMode::Append);
+    config.add_entry("general", "gaps_in = 7");
+
+    assert_eq!(
+        "general {\n    gaps_in_something = 1\n    col.active_border = rgb(000000)\n    nested {\n        gaps_in = 2\n    }\n    gaps_in = 5\n    col.active = rgb(ffffff)\n    gaps_in = 7\n}\n",
+        config.to_string()
+    );
+}
+
+#[test]
+fn repeated_categories() {
+    let config_str = "device {\n    name = mouse\n    sensitivity = 0\n}\n\ndevice {\n    name = touchpad\n    sensitivity = 0\n}\n\ndevice[pen] {\n    sensitivity = 0\n}\n";
+    let mut config = parse_config(config_str);
+    assert_eq!(config_str, config.to_string());
+
+    let names: Vec<_> = config
+        .categories("device")
+        .iter()
+        .map(|c| c.identifier())
+        .collect();
+    assert_eq!(vec![Some("mouse"), Some("touchpad"), Some("pen")], names);
+
+    config.add_entry("device[touchpad]", "sensitivity = 0.5");
+    config.add_entry("device[pen]", "sensitivity = 0.2");
+    config.add_entry("device[tablet]", "sensitivity = 1");
+
+    assert_eq!("0", config.get("device[mouse].sensitivity").unwrap().value);
+    assert_eq!(
+        "0.5",
+        config.get("device[touchpad]:sensitivity").unwrap().value
+    );
+    assert_eq!("0.2", config.get("device[pen].sensitivity").unwrap().value);
+    assert_eq!(4, config.get_all("device.sensitivity").len());
+    assert!(config
+        .to_string()
+        .ends_with("\ndevice {\n    name = tablet\n    sensitivity = 1\n}\n\n"));
+
+    assert_eq!(1, config.remove_category("device[mouse]"));
+    assert_eq!(3, config.categories("device").len());
+}
+
+#[test]
+fn separators_in_identifiers() {
+    let mut config = parse_config(
+        "device {
+    name = hid-256c:006d-pen
+    sensitivity = 0
+}
+",
+    );
+
+    assert_eq!(1, config.categories("device[hid-256c:006d-pen]").len());
+    assert_eq!(
+        "0",
+        config
+            .get("device[hid-256c:006d-pen]:sensitivity")
+            .unwrap()
+            .value
+    );
+
+    config.add_entry("device[hid-256c:006d-pen]", "sensitivity = 0.5");
+    config.add_entry("device[foo.bar]", "sensitivity = 1");
+    assert_eq!(
+        "0.5",
+        config
+            .get("device[hid-256c:006d-pen].sensitivity")
+            .unwrap()
+            .value
+    );
+    assert_eq!(
+        "1",
+        config.get("device[foo.bar].sensitivity").unwrap().value
+    );
+    assert!(config
+        .to_string()
+        .ends_with("device {\n    name = foo.bar\n    sensitivity = 1\n}\n\n"));
+    assert!(config.content.category("device[foo.bar]").is_some());
+}
+
+#[test]
+fn colon_categories() {
+    let mut config = parse_config(
+        "decoration {\n    rounding = 5\n}\ndecoration:blur:enabled = true\ngeneral {\n    col:active_border = rgb(000000)\n}\n",
+    );
+
+    assert_eq!("true", config.get("decoration.blur.enabled").unwrap().value);
+    assert_eq!(
+        "rgb(000000)",
+        config.get("general:col.active_border").unwrap().value
+    );
+
+    config.add_entry("decoration.blur", "enabled = false");
+    config.add_entry("general.col", "active_border = rgb(ffffff)");
+    assert_eq!(
+        "decoration {\n    rounding = 5\n}\ndecoration:blur:enabled = false\ngeneral {\n    col:active_border = rgb(ffffff)\n}\n",
+        config.to_string()
+    );
+
+    config.entry_style = hyprparser::EntryStyle::Colon;
+    config.add_entry("decoration.blur", "size = 8");
+    config.add_entry("device[mouse]", "sensitivity = 1");
+    assert!(config
+        .to_string()
+        .ends_with("}\ndecoration:blur:size = 8\ndevice[mouse]:sensitivity = 1\n"));
+    assert_eq!("8", config.get("decoration:blur:size").unwrap().value);
+    assert_eq!("1", config.get("device[mouse].sensitivity").unwrap().value);
+
+    // Adding again replaces the entry rather than appending another
+    config.add_entry("device[mouse]", "sensitivity = 2");
+    assert_eq!(1, config.get_all("device[mouse].sensitivity").len());
+    assert_eq!("2", config.get("device[mouse].sensitivity").unwrap().value);
+    assert!(config
+        .to_string()
+        .ends_with("}\ndecoration:blur:size = 8\ndevice[mouse]:sensitivity = 2\n"));
+
+    config.add_entry("", "exec-once = waybar");
+    assert!(config.to_string().ends_with("\nexec-once = waybar\n"));
+    assert_eq!("waybar", config.get("exec-once").unwrap().value);
+}
+
+#[test]
+fn variables() {
+    let dir = std::env::temp_dir().join("hyprparser_variables");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("colors.conf"), "$accent = rgb(ffffff)\n").unwrap();
+
+    let mut config = parse_config(&format!(
+        "# header\n$dir = {}\n$name = colors\nsource = $dir/$name.conf\n$mod = SUPER\n$modShift = $mod SHIFT\n$mod = ALT\n",
+        dir.display()
+    ));
+    assert!(config.diagnostics.is_empty());
+
+    let names: Vec<_> = config.variables().iter().map(|v| v.name).collect();
+    assert_eq!(
+        vec!["dir", "name", "accent", "mod", "modShift", "mod"],
+        names
+    );
+    assert_eq!(
+        "SUPER SHIFT",
+        config.get_variable("$modShift").unwrap().value
+    );
+    assert_eq!("ALT", config.get_variable("mod").unwrap().value);
+    assert_eq!(
+        "ALT, SUPER SHIFT, $none",
+        config.expand("$mod, $modShift, $none")
+    );
+
+    config.set_variable("mod", "CTRL");
+    config.set_variable("$terminal", "kitty");
+    assert_eq!("CTRL", config.get_variable("mod").unwrap().value);
+    assert!(config.to_string().ends_with("$mod = CTRL\n"));
+    assert!(config
+        .to_string()
+        .starts_with("# header\n$terminal = kitty\n$dir"));
+}
+
+#[test]
+fn recursive_sourcing() {
+    use hyprparser::ParseErrorKind;
+
+    let dir = std::env::temp_dir().join("hyprparser_recursive_sourcing");
+    let conf_d = dir.join("conf.d");
+    fs::create_dir_all(&conf_d).unwrap();
+    fs::write(
+        dir.join("looks.conf"),
+        format!(
+            "source = {}/conf.d/*.conf\ngeneral {{\n    gaps_in = 1\n}}\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    fs::write(conf_d.join("b.conf"), "general {\n    gaps_in = 3\n}\n").unwrap();
+    fs::write(
+        conf_d.join("a.conf"),
+        format!(
+            "general {{\n    gaps_in = 2\n}}\nsource = {}/looks.conf\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    fs::write(
+        conf_d.join(".hidden.conf"),
+        "general {\n    gaps_in = 9\n}\n",
+    )
+    .unwrap();
+
+    let config = parse_config(&format!(
+        "general {{\n    gaps_in = 0\n}}\nsource = {}/looks.conf\n",
+        dir.display()
+    ));
+
+    let values: Vec<_> = config
+        .get_all("general.gaps_in")
+        .iter()
+        .map(|e| e.value)
+        .collect();
+    assert_eq!(vec!["0", "2", "3", "1"], values);
+
+    let includes = config.includes();
+    assert_eq!(1, includes.len());
+    assert_eq!(Some(4), includes[0].file.line);
+    assert!(includes[0]
+        .file
+        .path
+        .as_ref()
+        .unwrap()
+        .ends_with("looks.conf"));
+    let nested: Vec<_> = includes[0]
+        .includes
+        .iter()
+        .map(|i| i.file.path.as_ref().unwrap())
+        .collect();
+    assert!(nested[0].ends_with("conf.d/a.conf") && nested[1].ends_with("conf.d/b.conf"));
+    assert!(includes[0].includes[0].includes.is_empty());
+
+    assert_eq!(1, config.diagnostics.len());
+    assert!(matches!(
+        config.diagnostics[0].kind,
+        ParseErrorKind::SourceCycle { .. }
+    ));
+    assert!(config.diagnostics[0]
+        .path
+        .as_ref()
+        .unwrap()
+        .ends_with("conf.d/a.conf"));
+
+    let config = parse_config(&format!("source = {}/conf.d/*.missing\n", dir.display()));
+    assert!(matches!(
+        config.diagnostics[0].kind,
+        ParseErrorKind::Source { .. }
+    ));
+}
+
+#[test]
+fn source_path_resolving() {
+    use hyprparser::Dirs;
+
+    let dir = std::env::temp_dir().join("hyprparser_source_path_resolving");
+    fs::create_dir_all(dir.join("home/.config/hypr")).unwrap();
+    fs::create_dir_all(dir.join("dotfiles/hypr/conf")).unwrap();
+    fs::write(dir.join("home/.config/hypr/looks.conf"), "$from = config\n").unwrap();
+    fs::write(dir.join("home/theme.conf"), "$theme = home\n").unwrap();
+    fs::write(
+        dir.join("dotfiles/hypr/looks.conf"),
+        "source = conf/binds.conf\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("dotfiles/hypr/conf/binds.conf"),
+        "$from = dotfiles\n",
+    )
+    .unwrap();
+
+    let dirs = Dirs {
+        home: dir.join("home"),
+        config: dir.join("home/.config/hypr"),
+    };
+    let config_str = "source = looks.conf\nsource = ~/theme.conf\nsource = $HOME/theme.conf\n";
+
+    let mut config = HyprlandConfig::new();
+    config.dirs = dirs.clone();
+    config.parse(config_str, false);
+    assert!(config.diagnostics.is_empty());
+    assert_eq!("config", config.get_variable("from").unwrap().value);
+    assert_eq!(3, config.includes().len());
+
+    let mut config = HyprlandConfig::new();
+    config.dirs = dirs.clone();
+    config.path = Some(dir.join("dotfiles/hypr/hyprland.conf"));
+    config.parse(config_str, false);
+    assert!(config.diagnostics.is_empty());
+    assert_eq!("dotfiles", config.get_variable("from").unwrap().value);
+    assert_eq!("home", config.get_variable("theme").unwrap().value);
+    assert_eq!(
+        Some(dir.join("dotfiles/hypr/conf/binds.conf")),
+        config.includes()[0].includes[0].file.path
+    );
+
+    // A `$HOME` defined in the config wins over the home directory
+    fs::write(dir.join("dotfiles/hypr/theme.conf"), "$theme = dotfiles\n").unwrap();
+    let mut config = HyprlandConfig::new();
+    config.dirs = dirs;
+    config.parse(
+        &format!(
+            "$HOME = {}\nsource = $HOME/theme.conf\n",
+            dir.join("dotfiles/hypr").display()
+        ),
+        false,
+    );
+    assert!(config.diagnostics.is_empty());
+    assert_eq!("dotfiles", config.get_variable("theme").unwrap().value);
+}
+
+#[test]
+fn loading_and_saving() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join("hyprparser_loading_and_saving");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let main_path = dir.join("hyprland.conf");
+    let sourced_path = dir.join("looks.conf");
+    fs::write(
+        &main_path,
+        "source = looks.conf\ninput {\n    sensitivity = 0\n}\n",
+    )
+    .unwrap();
+    fs::write(&sourced_path, "general {\n    gaps_in = 5\n}\n").unwrap();
+    fs::set_permissions(&main_path, fs::Permissions::from_mode(0o600)).unwrap();
+
+    let mut config = HyprlandConfig::load(&main_path).unwrap();
+    assert_eq!("5", config.get("general.gaps_in").unwrap().value);
+
+    config.add_entry("input", "sensitivity = 0.5");
+    config.add_entry("general", "gaps_in = 10");
+    assert_eq!(
+        "general {\n    gaps_in = 5\n}\n",
+        fs::read_to_string(&sourced_path).unwrap()
+    );
+    assert_eq!(vec![sourced_path.as_path()], config.dirty_files());
+
+    config.save().unwrap();
+    assert!(config.dirty_files().is_empty());
+
+    assert_eq!(
+        "source = looks.conf\ninput {\n    sensitivity = 0.5\n}\n",
+        fs::read_to_string(&main_path).unwrap()
+    );
+    assert_eq!(
+        "general {\n    gaps_in = 10\n}\n",
+        fs::read_to_string(&sourced_path).unwrap()
+    );
+    assert_eq!(
+        0o600,
+        fs::metadata(&main_path).unwrap().permissions().mode() & 0o777
+    );
+    assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+    let copy_path = dir.join("copy.conf");
+    config.save_to(&copy_path).unwrap();
+    assert_eq!(config.to_string(), fs::read_to_string(&copy_path).unwrap());
+
+    assert!(HyprlandConfig::new().save().is_err());
+    assert!(HyprlandConfig::load(dir.join("missing.conf")).is_err());
+
+    // Saves of the same file from several threads never mix their contents
+    let contents: Vec<String> = (0..8)
+        .map(|i| format!("$value = {}\n", i.to_string().repeat(100_000)))
+        .collect();
+    std::thread::scope(|scope| {
+        for text in &contents {
+            let copy_path = &copy_path;
+            scope.spawn(move || {
+                hyprparser::ConfigFs::write(&hyprparser::RealFs, copy_path, text).unwrap()
+            });
+        }
+    });
+    assert!(contents.contains(&fs::read_to_string(&copy_path).unwrap()));
+    assert_eq!(3, fs::read_dir(&dir).unwrap().count());
+}
+
+#[test]
+fn in_memory_filesystem() {
+    use hyprparser::{ConfigFs, MemoryFs, ParseErrorKind};
+    use std::path::Path;
+
+    let fs = MemoryFs::new()
+        .with_file(
+            "/hypr/hyprland.conf",
+            "source = ./conf.d/*.conf\nsource = /hypr/missing.conf\n",
+        )
+        .with_file("/hypr/conf.d/b.conf", "$b = 2\nsource = ../hyprland.conf\n")
+        .with_file("/hypr/conf.d/a.conf", "general {\n    gaps_in = 5\n}\n");
+
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    let paths: Vec<_> = config
+        .includes()
+        .iter()
+        .filter_map(|i| i.file.path.as_deref())
+        .collect();
+    assert_eq!(
+        vec![
+            Path::new("/hypr/conf.d/a.conf"),
+            Path::new("/hypr/conf.d/b.conf")
+        ],
+        paths
+    );
+    assert_eq!("2", config.get_variable("b").unwrap().value);
+
+    let kinds: Vec<_> = config.diagnostics.iter().map(|d| &d.kind).collect();
+    assert!(matches!(
+        kinds[..],
+        [
+            ParseErrorKind::SourceCycle { .. },
+            ParseErrorKind::Source { .. }
+        ]
+    ));
+
+    let untouched = fs.metadata(Path::new("/hypr/conf.d/b.conf")).unwrap();
+    config.add_entry("general", "gaps_in = 10");
+    config.save_with(&fs).unwrap();
+    assert_eq!(
+        Some("general {\n    gaps_in = 10\n}\n".to_string()),
+        fs.get("/hypr/conf.d/a.conf")
+    );
+    assert_eq!(
+        untouched,
+        fs.metadata(Path::new("/hypr/conf.d/b.conf")).unwrap()
+    );
+
+    config.save_to_with("/backup/hyprland.conf", &fs).unwrap();
+    assert_eq!(4, fs.paths().len());
+    assert!(fs.read(Path::new("/hypr/missing.conf")).is_err());
+}
+
+#[test]
+fn sourced_file_editing() {
+    use hyprparser::MemoryFs;
+    use std::path::Path;
+
+    let fs = MemoryFs::new()
+        .with_file(
+            "/hypr/hyprland.conf",
+            "$x = 0\nsource = ./a.conf\nsource = ./c.conf\ngeneral {\n    gaps_in = 9\n}\n",
+        )
+        .with_file(
+            "/hypr/a.conf",
+            "general {\n    gaps_in = 1\n}\nsource = ./b.conf",
+        )
+        .with_file("/hypr/b.conf", "$x = b\n")
+        .with_file("/hypr/c.conf", "$y = c\n");
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    assert_eq!(3, config.sourced_files().len());
+    assert_eq!(Some(0), config.sourced_files()[1].parent);
+
+    assert!(!config.inline_sourced(99));
+    assert!(config.detach_sourced(99).is_none());
+    assert!(config.inline_sourced(0));
+    assert_eq!(
+        "$x = 0\ngeneral {\n    gaps_in = 1\n}\nsource = ./b.conf\nsource = ./c.conf\ngeneral {\n    gaps_in = 9\n}\n",
+        config.to_string()
+    );
+    let lines: Vec<_> = config
+        .includes()
+        .iter()
+        .map(|i| (i.index, i.file.line))
+        .collect();
+    assert_eq!(vec![(0, Some(5)), (1, Some(6))], lines);
+    assert_eq!("b", config.get_variable("x").unwrap().value);
+    let lines: Vec<_> = config
+        .get_all("general.gaps_in")
+        .iter()
+        .map(|e| e.origin.line)
+        .collect();
+    assert_eq!(vec![Some(3), Some(8)], lines);
+
+    let detached = config.detach_sourced(1).unwrap();
+    assert_eq!(Some(Path::new("/hypr/c.conf")), detached.path.as_deref());
+    assert!(config.get_variable("y").is_none());
+    assert!(!config.to_string().contains("c.conf"));
+
+    let index = config.add_source("./d.conf", "$z = d\n");
+    config
+        .sourced_content_mut(0)
+        .unwrap()
+        .push(hyprparser::Node::from_line("$w = b", 0).unwrap());
+    assert_eq!(
+        vec![Path::new("/hypr/b.conf"), Path::new("/hypr/d.conf")],
+        config.dirty_files()
+    );
+    config.save_with(&fs).unwrap();
+    assert_eq!(Some("$z = d\n".to_string()), fs.get("/hypr/d.conf"));
+    assert_eq!("d", config.get_variable("z").unwrap().value);
+    assert!(!config.sourced_files()[index].is_dirty());
+}
+
+#[test]
+fn flattening_and_extracting() {
+    use hyprparser::MemoryFs;
+
+    let fs = MemoryFs::new()
+        .with_file(
+            "/hypr/hyprland.conf",
+            "source = conf.d/*.conf\nsource = missing.conf\ngeneral {\n    gaps_in = 0\n}\n",
+        )
+        .with_file("/hypr/conf.d/a.conf", "$a = 1\nsource = ../nested.conf")
+        .with_file("/hypr/conf.d/b.conf", "$b = 2\n")
+        .with_file("/hypr/nested.conf", "$nested = 3\n");
+    let config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+
+    assert_eq!(
+        "$a = 1\n$nested = 3\n$b = 2\nsource = missing.conf\ngeneral {\n    gaps_in = 0\n}\n",
+        config.flatten(false).to_string()
+    );
+
+    let mut config = parse_config(
+        "decoration {\n    rounding = 5\n}\ndecoration:blur:size = 3\ngeneral {\n    gaps_in = 1\n}\ndecoration:rounding = 8\n",
+    );
+    let index = config
+        .extract_to_source("decoration", "/hypr/decoration.conf")
+        .unwrap();
+    assert!(config
+        .extract_to_source("animations", "/hypr/animations.conf")
+        .is_none());
+
+    assert_eq!(
+        "source = /hypr/decoration.conf\ngeneral {\n    gaps_in = 1\n}\n",
+        config.to_string()
+    );
+    assert_eq!(
+        "decoration {\n    rounding = 5\n}\ndecoration:blur:size = 3\ndecoration:rounding = 8\n",
+        config.sourced_files()[index].content.to_string()
+    );
+    assert_eq!("8", config.get("decoration.rounding").unwrap().value);
+    assert_eq!(
+        "decoration {\n    rounding = 5\n}\ndecoration:blur:size = 3\ndecoration:rounding = 8\ngeneral {\n    gaps_in = 1\n}\n",
+        config.flatten(false).to_string()
+    );
+
+    config.save_to_with("/hypr/hyprland.conf", &fs).unwrap();
+    assert!(fs.get("/hypr/decoration.conf").is_some());
+
+    // `source` directives stay with the files they load
+    let fs = MemoryFs::new()
+        .with_file("/hypr/hyprland.conf", "source = a.conf\n$x = 1\n")
+        .with_file("/hypr/a.conf", "$y = 2\n");
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    let is_source = |node: &hyprparser::Node| matches!(node, hyprparser::Node::Source(_));
+    assert_eq!(None, config.extract_to_source(is_source, "/hypr/b.conf"));
+    config
+        .extract_to_source(
+            |node: &hyprparser::Node| is_source(node) || node.to_string().starts_with("$x"),
+            "/hypr/b.conf",
+        )
+        .unwrap();
+    assert_eq!("2", config.get_variable("y").unwrap().value);
+    assert_eq!("1", config.get_variable("x").unwrap().value);
+    assert_eq!(
+        "# >>> /hypr/a.conf\n$y = 2\n# <<< /hypr/a.conf\n# >>> /hypr/b.conf\n$x = 1\n# <<< /hypr/b.conf\n",
+        config.flatten(true).to_string()
+    );
+}
+
+#[test]
+fn config_diff() {
+    use hyprparser::EntryChange;
+
+    let old = parse_config(
+        "$mod = SUPER\ngeneral {\n    gaps_in = 5\n    border_size = 2\n}\nbind = $mod, Q, exec, kitty\n",
+    );
+    let new = parse_config(
+        "$mod = ALT\ngeneral {\n    gaps_in = 10\n}\nbind = $mod, Q, exec, kitty\nbind = $mod, C, killactive\n",
+    );
+
+    assert!(old.diff(&old).is_empty());
+    assert_eq!(
+        vec![
+            EntryChange::Changed {
+                name: "$mod".to_string(),
+                old: "SUPER".to_string(),
+                new: "ALT".to_string(),
+            },
+            EntryChange::Changed {
+                name: "general.gaps_in".to_string(),
+                old: "5".to_string(),
+                new: "10".to_string(),
+            },
+            EntryChange::Removed {
+                name: "general.border_size".to_string(),
+                value: "2".to_string(),
+            },
+            EntryChange::Added {
+                name: "bind".to_string(),
+                value: "$mod, C, killactive".to_string(),
+            },
+        ],
+        old.diff(&new)
+    );
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn config_watching() {
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join("hyprparser_config_watching");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("conf.d")).unwrap();
+    let main_path = dir.join("hyprland.conf");
+    let sourced_path = dir.join("conf.d/looks.conf");
+    fs::write(
+        &main_path,
+        "source = conf.d/looks.conf\nsource = themes/*.conf\n",
+    )
+    .unwrap();
+    fs::write(&sourced_path, "general {\n    gaps_in = 5\n}\n").unwrap();
+
+    let mut watcher = HyprlandConfig::load(&main_path).unwrap().watch().unwrap();
+    assert_eq!(None, watcher.try_recv().unwrap());
+
+    fs::write(&sourced_path, "general {\n    gaps_in = 10\n}\n").unwrap();
+    let event = watcher
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(vec![sourced_path.clone()], event.files);
+    assert_eq!(
+        vec![hyprparser::EntryChange::Changed {
+            name: "general.gaps_in".to_string(),
+            old: "5".to_string(),
+            new: "10".to_string(),
+        }],
+        event.changes
+    );
+    assert_eq!("10", watcher.config().get("general.gaps_in").unwrap().value);
+
+    fs::write(dir.join("unrelated.txt"), "").unwrap();
+    assert_eq!(
+        None,
+        watcher.recv_timeout(Duration::from_millis(300)).unwrap()
+    );
+
+    // Files matching a pattern that matched nothing are picked up, even in a
+    // directory created after loading
+    fs::create_dir(dir.join("themes")).unwrap();
+    assert_eq!(
+        None,
+        watcher.recv_timeout(Duration::from_millis(300)).unwrap()
+    );
+    let theme_path = dir.join("themes/dark.conf");
+    fs::write(&theme_path, "$theme = dark\n").unwrap();
+    let event = watcher
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(vec![theme_path], event.files);
+    assert_eq!(
+        "dark",
+        watcher.config().get_variable("theme").unwrap().value
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn save_conflicts() {
+    use hyprparser::{MemoryFs, SaveError};
+
+    let fs = MemoryFs::new()
+        .with_file("/hypr/hyprland.conf", "source = looks.conf\n$mod = SUPER\n")
+        .with_file(
+            "/hypr/looks.conf",
+            "general {\n    gaps_in = 5\n    gaps_out = 20\n    border_size = 2\n}\n",
+        );
+
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    config.add_entry("general", "gaps_in = 10");
+    fs.insert(
+        "/hypr/looks.conf",
+        "general {\n    gaps_in = 5\n    gaps_out = 20\n    border_size = 4\n}\n",
+    );
+
+    match config.save_with(&fs) {
+        Err(SaveError::Conflict(conflicts)) => {
+            assert_eq!(1, conflicts.len());
+            assert_eq!(std::path::Path::new("/hypr/looks.conf"), conflicts[0].path);
+        }
+        result => panic!("expected a conflict, got {:?}", result),
+    }
+    assert!(fs.get("/hypr/looks.conf").unwrap().contains("gaps_in = 5"));
+
+    config.save_merged_with(&fs).unwrap();
+    assert_eq!(
+        "general {\n    gaps_in = 10\n    gaps_out = 20\n    border_size = 4\n}\n",
+        fs.get("/hypr/looks.conf").unwrap()
+    );
+    assert_eq!("4", config.get("general.border_size").unwrap().value);
+    assert!(config.dirty_files().is_empty());
+
+    config.set_variable("mod", "ALT");
+    fs.insert("/hypr/hyprland.conf", "source = looks.conf\n$mod = CTRL\n");
+    assert!(matches!(
+        config.save_merged_with(&fs),
+        Err(SaveError::Conflict(_))
+    ));
+
+    // Only touching a file isn't a conflict
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    fs.insert("/hypr/hyprland.conf", "source = looks.conf\n$mod = CTRL\n");
+    config.set_variable("mod", "ALT");
+    config.save_with(&fs).unwrap();
+    assert_eq!(
+        "source = looks.conf\n$mod = ALT\n",
+        fs.get("/hypr/hyprland.conf").unwrap()
+    );
+
+    // Nor does keeping the modification time hide a change, as `cp -p` does
+    let dir = std::env::temp_dir().join(format!("hyprparser-mtime-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("hyprland.conf");
+    fs::write(&path, "$mod = SUPER\n").unwrap();
+    let mut config = HyprlandConfig::load(&path).unwrap();
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+    fs::write(&path, "$mod = CTRL\nbind = x\n").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    config.set_variable("mod", "ALT");
+    assert!(matches!(config.save(), Err(SaveError::Conflict(_))));
+    assert_eq!(
+        "$mod = CTRL\nbind = x\n",
+        fs::read_to_string(&path).unwrap()
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn backups() {
+    use hyprparser::{Backup, MemoryFs};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let fs = MemoryFs::new()
+        .with_file("/hypr/hyprland.conf", "source = looks.conf\n")
+        .with_file("/hypr/looks.conf", "general {\n    gaps_in = 5\n}\n")
+        .with_file("/hypr/hyprland.conf.bak.2020-10-18T10:00:00", "old\n")
+        .with_file("/hypr/hyprland.conf.bak.2020-10-18T09:00:00", "older\n")
+        .with_file("/hypr/hyprland.conf.bak.not-a-time", "")
+        .with_file("/hypr/hyprland.conf.bak.2020-13-01T00:00:00", "");
+
+    let backups = Backup::of("/hypr/hyprland.conf", &fs);
+    assert_eq!(2, backups.len());
+    assert_eq!(
+        std::path::Path::new("/hypr/hyprland.conf.bak.2020-10-18T09:00:00"),
+        backups[0].path
+    );
+    assert_eq!(
+        UNIX_EPOCH + Duration::from_secs(1603015200),
+        backups[1].time
+    );
+
+    // Nothing is backed up by default
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+    config.add_entry("general", "gaps_in = 10");
+    config.save_with(&fs).unwrap();
+    assert!(Backup::of("/hypr/looks.conf", &fs).is_empty());
+
+    config.keep_backups = 2;
+    config.add_entry("general", "gaps_in = 15");
+    config.add_entry("input", "sensitivity = 0.5");
+    config.save_with(&fs).unwrap();
+
+    let backups = config.backups_with(&fs);
+    let paths: Vec<_> = backups.iter().map(|backup| &backup.original).collect();
+    assert_eq!(
+        vec![
+            "/hypr/hyprland.conf",
+            "/hypr/hyprland.conf",
+            "/hypr/looks.conf"
+        ],
+        paths
+    );
+    assert_eq!("old\n", fs.get(&backups[0].path).unwrap());
+    assert_eq!("source = looks.conf\n", fs.get(&backups[1].path).unwrap());
+    assert_eq!(None, fs.get("/hypr/hyprland.conf.bak.2020-10-18T09:00:00"));
+
+    config.restore_with(&backups[2], &fs).unwrap();
+    assert_eq!("10", config.get("general.gaps_in").unwrap().value);
+    assert_eq!(
+        "general {\n    gaps_in = 10\n}\n",
+        fs.get("/hypr/looks.conf").unwrap()
+    );
+}
+
+#[test]
+fn provenance() {
+    use hyprparser::{MemoryFs, Origin};
+    use std::path::Path;
+
+    let fs = MemoryFs::new()
+        .with_file(
+            "/hypr/hyprland.conf",
+            "$mod = SUPER\n\n  source = looks.conf\n",
+        )
+        .with_file(
+            "/hypr/looks.conf",
+            "general {\n    gaps_in = 5\n}\nsource = looks/*.conf\n",
+        )
+        .with_file(
+            "/hypr/looks/gaps.conf",
+            "# gaps\ngeneral {\n    gaps_out = 20\n}\n",
+        );
+    let mut config = HyprlandConfig::load_with("/hypr/hyprland.conf", &fs).unwrap();
+
+    let gaps_out = config.get("general.gaps_out").unwrap();
+    assert_eq!(
+        Origin {
+            file: Some(Path::new("/hypr/looks/gaps.conf")),
+            line: Some(3),
+            column: Some(5),
+            include_chain: vec![
+                Origin {
+                    file: Some(Path::new("/hypr/hyprland.conf")),
+                    line: Some(3),
+                    column: Some(3),
+                    include_chain: Vec::new(),
+                },
+                Origin {
+                    file: Some(Path::new("/hypr/looks.conf")),
+                    line: Some(4),
+                    column: Some(1),
+                    include_chain: Vec::new(),
+                },
+            ],
+        },
+        gaps_out.origin
+    );
+    assert_eq!(
+        "/hypr/looks/gaps.conf:3:5, sourced at /hypr/looks.conf:4:1, sourced at /hypr/hyprland.conf:3:3",
+        gaps_out.origin.to_string()
+    );
+
+    let variable = config.get_variable("mod").unwrap();
+    assert_eq!(Some(Path::new("/hypr/hyprland.conf")), variable.origin.file);
+    assert!(variable.origin.include_chain.is_empty());
+
+    let category = &config.categories("general")[0];
+    let gaps_in = category
+        .children
+        .iter()
+        .find(|node| matches!(node, hyprparser::Node::Assignment(_)))
+        .unwrap();
+    assert_eq!(
+        "/hypr/looks.conf:2:5, sourced at /hypr/hyprland.conf:3:3",
+        config.origin_of(gaps_in).unwrap().to_string()
+    );
+    assert_eq!(None, config.origin_of(&hyprparser::Node::blank()));
+
+    config.add_entry("general", "border_size = 2");
+    let border_size = config.get("general.border_size").unwrap();
+    assert_eq!(
+        (None, 1),
+        (
+            border_size.origin.line,
+            border_size.origin.include_chain.len()
+        )
+    );
+}
+
+#[test]
+fn colors_and_gradients() {
+    use hyprparser::{Color, ColorErrorKind, Gradient};
+
+    let config = parse_config("general {\n    col.active_border = rgba(33ccffee) rgba(00ff99ee) 45deg\n    col.inactive_border = 0xff595959\n}\n");
+
+    let text = config.get("general.col.active_border").unwrap().value;
+    let gradient: Gradient = text.parse().unwrap();
+    assert_eq!(
+        Gradient {
+            stops: vec![
+                Color::from_rgba8(0x33, 0xcc, 0xff, 0xee),
+                Color::from_rgba8(0x00, 0xff, 0x99, 0xee),
+            ],
+            angle: Some(45.0),
+        },
+        gradient
+    );
+    assert_eq!(text, gradient.to_string());
+
+    let inactive: Gradient = config
+        .get("general.col.inactive_border")
+        .unwrap()
+        .value
+        .parse()
+        .unwrap();
+    assert_eq!(
+        Gradient::from(Color::from_rgba8(0x59, 0x59, 0x59, 0xff)),
+        inactive
+    );
+    assert_eq!("0xff595959", inactive.to_string());
+    assert_eq!(
+        "rgba(595959ff)",
+        inactive.to_string_with(hyprparser::ColorStyle::Rgba)
+    );
+
+    let color = Color::new(0.5, 0.25, 1.0, 0.0);
+    assert_eq!(
+        color.to_rgba8(),
+        color.to_string().parse::<Color>().unwrap().to_rgba8()
+    );
+    assert_eq!(
+        "rgba(8040ff00) 22.5deg",
+        Gradient {
+            stops: vec![color],
+            angle: Some(22.5)
+        }
+        .to_string()
+    );
+
+    let error = |text: &str| text.parse::<Gradient>().unwrap_err().kind;
+    assert_eq!(ColorErrorKind::Empty, error(" 45deg"));
+    assert_eq!(ColorErrorKind::InvalidAngle, error("rgb(ffffff) fortydeg"));
+    assert_eq!(ColorErrorKind::UnknownNotation, error("rgb(ffffff) red"));
+    assert_eq!(ColorErrorKind::Unclosed, error("rgba(ffffffff"));
+    assert_eq!(ColorErrorKind::InvalidHex, error("rgb(gggggg)"));
+    assert_eq!(
+        ColorErrorKind::HexLength {
+            expected: 8,
+            found: 6
+        },
+        error("rgba(ffffff)")
+    );
+    assert_eq!(
+        "invalid color `rgba(ffffff)`: expected 8 hex digits, or 4 in the short form, found 6",
+        "rgba(ffffff)".parse::<Color>().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn color_notations() {
+    use hyprparser::{color, Color, ColorErrorKind, Gradient};
+
+    let parse = |text: &str| color::parse(text).ok().map(<(f32, f32, f32, f32)>::from);
+    let orange = Some((1.0, 0.53333336, 0.0, 0.53333336));
+    for text in [
+        "rgba(ff880088)",
+        "rgba(f808)",
+        "  rgba( FF880088 )  ",
+        "rgba(255, 136, 0, 0.53333336)",
+        "rgba(255,136,0,0.53333336)",
+        "0x88ff8800",
+        "2298447872",
+    ] {
+        assert_eq!(orange, parse(text), "{}", text);
+    }
+    assert_eq!(
+        Some((1.0, 0.5019608, 0.0, 1.0)),
+        parse(" rgb(255, 128, 0) ")
+    );
+    assert_eq!(Some((1.0, 0.8, 0.0, 1.0)), parse("rgb( fc0 )"));
+    assert_eq!(Some((1.0, 1.0, 1.0, 1.0)), parse("-1"));
+
+    let gradient: Gradient = "rgba(255, 136, 0, 0.5)  rgb(0, 0, 0) 90deg"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        vec![
+            Color::new(1.0, 0.53333336, 0.0, 0.5),
+            Color::new(0.0, 0.0, 0.0, 1.0)
+        ],
+        gradient.stops
+    );
+    assert_eq!(Some(90.0), gradient.angle);
+
+    let error = |text: &str| text.parse::<Color>().unwrap_err().kind;
+    assert_eq!(ColorErrorKind::Empty, error("   "));
+    assert_eq!(
+        ColorErrorKind::ComponentCount {
+            expected: 4,
+            found: 3
+        },
+        error("rgba(255, 128, 0)")
+    );
+    assert_eq!(
+        ColorErrorKind::InvalidComponent("half".to_string()),
+        error("rgba(255, 128, 0, half)")
+    );
+    assert_eq!(
+        ColorErrorKind::OutOfRange("256".to_string()),
+        error("rgb(256, 0, 0)")
+    );
+    assert_eq!(
+        ColorErrorKind::OutOfRange("1.5".to_string()),
+        error("rgba(255, 0, 0, 1.5)")
+    );
+    assert_eq!(
+        ColorErrorKind::HexLength {
+            expected: 6,
+            found: 5
+        },
+        error("rgb(fffff)")
+    );
+    assert_eq!(ColorErrorKind::InvalidHex, error("0x"));
+    assert_eq!(
+        ColorErrorKind::OutOfRange("1ffffffff".to_string()),
+        error("0x1ffffffff")
+    );
+    assert_eq!(ColorErrorKind::UnknownNotation, error("hsl(0, 0%, 0%)"));
+    assert_eq!(
+        "invalid color `rgb(256, 0, 0)`: `256` is out of range",
+        "rgb(256, 0, 0)".parse::<Color>().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn color_formatting() {
+    use hyprparser::{color, Color, ColorStyle, Gradient};
+
+    assert_eq!("rgba(80808080)", color::format(0.5, 0.5, 0.5, 0.5));
+    assert_eq!("rgba(00ff0000)", color::format(-1.0, 2.0, 0.0, 0.0));
+
+    // Every 8-bit value survives formatting and parsing again, in every style
+    for value in 0..=255u8 {
+        let color = Color::from_rgba8(value, 255 - value, value / 2, value);
+        for style in [
+            ColorStyle::Rgba,
+            ColorStyle::Rgb,
+            ColorStyle::Argb,
+            ColorStyle::RgbaDecimal,
+            ColorStyle::RgbDecimal,
+        ] {
+            let text = color.to_string_with(style);
+            let parsed: Color = text.parse().unwrap();
+            assert_eq!(color.to_rgba8(), parsed.to_rgba8(), "{}", text);
+        }
+        let (red, green, blue, alpha) = color.into();
+        let text = color::format(red, green, blue, alpha);
+        assert_eq!(Ok(color), color::parse(&text));
+    }
+
+    let color = Color::new(1.0, 0.5, 0.0, 0.8);
+    assert_eq!("rgba(ff8000cc)", color.to_string());
+    assert_eq!("rgba(ff8000cc)", color.to_string_with(ColorStyle::Rgb));
+    assert_eq!("0xccff8000", color.to_string_with(ColorStyle::Argb));
+    assert_eq!(
+        "rgba(255, 128, 0, 0.8)",
+        color.to_string_with(ColorStyle::RgbaDecimal)
+    );
+    assert_eq!(
+        "rgba(255, 128, 0, 0.8)",
+        color.to_string_with(ColorStyle::RgbDecimal)
+    );
+    assert_eq!(
+        "rgb(255, 128, 0)",
+        Color {
+            alpha: 1.0,
+            ..color
+        }
+        .to_string_with(ColorStyle::RgbDecimal)
+    );
+    assert_eq!("0xccff8000", color.with_style(ColorStyle::Argb).to_string());
+
+    // Parsed colors keep their notation, apart from short forms and plain numbers
+    for (text, kept) in [
+        ("rgba(33CCFFEE)", "rgba(33ccffee)"),
+        ("rgb(33ccff)", "rgb(33ccff)"),
+        ("rgb(3cf)", "rgb(33ccff)"),
+        ("0xee33ccff", "0xee33ccff"),
+        ("-1", "0xffffffff"),
+        ("rgba(51, 204, 255, 0.5)", "rgba(51, 204, 255, 0.5)"),
+        ("rgb(51,204,255)", "rgb(51, 204, 255)"),
+    ] {
+        assert_eq!(kept, text.parse::<Color>().unwrap().to_string());
+    }
+
+    let gradient: Gradient = "rgb(33ccff) 0xee00ff99 45deg".parse().unwrap();
+    assert_eq!("rgb(33ccff) 0xee00ff99 45deg", gradient.to_string());
+    assert_eq!(
+        "rgba(33ccffff) rgba(00ff99ee) 45deg",
+        gradient.to_string_with(ColorStyle::Rgba)
+    );
+}
+
+#[test]
+fn color_conversions() {
+    use hyprparser::color::{self, Color};
+    use hyprparser::ColorErrorKind;
+
+    let (hue, saturation, lightness) = color::rgb_to_hsl(1.0, 0.5, 0.0);
+    assert_eq!((30.0, 1.0, 0.5), (hue, saturation, lightness));
+    assert_eq!((1.0, 0.5, 0.0), color::hsl_to_rgb(30.0, 1.0, 0.5));
+    assert_eq!((210.0, 0.5, 0.8), color::rgb_to_hsv(0.4, 0.6, 0.8));
+    assert_eq!((0.0, 0.0, 0.5), color::rgb_to_hsv(0.5, 0.5, 0.5));
+    assert_eq!((1.0, 0.0, 0.0), color::hsv_to_rgb(-360.0, 1.0, 1.0));
+
+    // Every 8-bit color survives a round trip through HSL and HSV
+    for value in (0..=255u8).step_by(5) {
+        let color = Color::from_rgba8(value, 255 - value, value / 3, 255);
+        let (hue, saturation, lightness) = color.to_hsl();
+        assert_eq!(color, Color::from_hsl(hue, saturation, lightness, 1.0));
+        let (hue, saturation, value) = color.to_hsv();
+        assert_eq!(color, Color::from_hsv(hue, saturation, value, 1.0));
+    }
+
+    assert_eq!(
+        Ok(Color::from_rgba8(255, 136, 0, 255)),
+        color::parse_css_hex("#ff8800")
+    );
+    assert_eq!(
+        Ok(Color::from_rgba8(255, 136, 0, 136)),
+        color::parse_css_hex("#f808")
+    );
+    assert_eq!(
+        ColorErrorKind::MissingHash,
+        color::parse_css_hex("ff8800").unwrap_err().kind
+    );
+    assert_eq!(
+        ColorErrorKind::HexLength {
+            expected: 8,
+            found: 5
+        },
+        color::parse_css_hex("#fffff").unwrap_err().kind
+    );
+    assert_eq!("#ff8000", color::to_css_hex(Color::new(1.0, 0.5, 0.0, 1.0)));
+    assert_eq!("#ff8000cc", Color::new(1.0, 0.5, 0.0, 0.8).to_css_hex());
+}
+
+#[test]
+fn typed_values() {
+    use hyprparser::{parse_config, Color, Gaps, Gradient, Value};
+
+    for (text, expected) in [
+        ("true", Value::Bool(true)),
+        ("Off", Value::Bool(false)),
+        ("1", Value::Int(1)),
+        ("-20", Value::Int(-20)),
+        ("0x1F", Value::Int(31)),
+        ("0.5", Value::Float(0.5)),
+        ("-1e2", Value::Float(-100.0)),
+        ("5 10", Value::Vec2(5.0, 10.0)),
+        ("0.5 -1", Value::Vec2(0.5, -1.0)),
+        ("5 10 15", Value::Gaps(Gaps::new(5, 10, 15, 10))),
+        ("5 10 15 20", Value::Gaps(Gaps::new(5, 10, 15, 20))),
+        (
+            "rgb(33ccff)",
+            Value::Color(Color::from_rgba8(0x33, 0xcc, 0xff, 255)),
+        ),
+        (
+            "rgba(33ccffee) rgba(00ff99ee) 45deg",
+            Value::Gradient("rgba(33ccffee) rgba(00ff99ee) 45deg".parse().unwrap()),
+        ),
+        ("inf", Value::String("inf".to_string())),
+        ("1e400", Value::String("1e400".to_string())),
+        ("1 1e400", Value::String("1 1e400".to_string())),
+        ("1 2 3 4 5", Value::String("1 2 3 4 5".to_string())),
+        (
+            "SUPER, Q, exec, kitty",
+            Value::String("SUPER, Q, exec, kitty".to_string()),
+        ),
+    ] {
+        assert_eq!(expected, Value::parse(text), "{}", text);
+    }
+
+    assert_eq!(Some(false), Value::parse("0").as_bool());
+    assert_eq!(Some(1), Value::parse("yes").as_int());
+    assert_eq!(Some(2.0), Value::parse("2").as_float());
+    assert_eq!(Some(Gaps::all(5)), Value::parse("5").as_gaps());
+    assert_eq!(None, Value::parse("5.5 10").as_gaps());
+    assert_eq!(
+        Some(Color::from_rgba8(0x33, 0xcc, 0xff, 0xee)),
+        Value::parse("0xee33ccff").as_color()
+    );
+    assert_eq!(
+        Some(Gradient::from(Color::new(1.0, 1.0, 1.0, 1.0))),
+        Value::parse("-1").as_gradient()
+    );
+    assert_eq!("5 10", Gaps::new(5, 10, 5, 10).to_string());
+    assert_eq!("5 10 15", Gaps::new(5, 10, 15, 10).to_string());
+    assert_eq!("5", Gaps::all(5).to_string());
+
+    let mut config = parse_config(
+        "$accent = rgb(33ccff)\n\ngeneral {\n    gaps_out = 20\n    col.inactive_border = $accent\n    allow_tearing = no\n}\n\nmisc {\n    vfr = 1\n}\n",
+    );
+    assert_eq!(
+        Some(Value::Color(Color::from_rgba8(0x33, 0xcc, 0xff, 255))),
+        config.get_value("general.col.inactive_border")
+    );
+    assert_eq!(None, config.get_value("general.layout"));
+
+    // Variables are expanded with the values they have where the entry is
+    let mut scoped =
+        parse_config("$a = 1\ngaps_in = $a\n$a = 2\n$c = 0xff33ccff\ncol = $c\n$c = 1\n");
+    assert_eq!(Some(Value::Int(1)), scoped.get_value("gaps_in"));
+    scoped.set("", "col", Color::new(1.0, 0.5, 0.0, 1.0));
+    assert_eq!("0xffff8000", scoped.get("col").unwrap().value);
+
+    // Reading a value and setting it back leaves the text as it was
+    let mut hex =
+        parse_config("general {\n    col.active_border = 0xff33ccff\n    border_size = 0x02\n}\n");
+    for key in ["col.active_border", "border_size"] {
+        let value = hex.get_value(&format!("general.{}", key)).unwrap();
+        hex.set("general", key, value);
+    }
+    hex.set("general", "border_size", 3);
+    assert_eq!(
+        "general {\n    col.active_border = 0xff33ccff\n    border_size = 0x03\n}\n",
+        hex.to_string()
+    );
+
+    config.set("general", "gaps_out", Gaps::new(5, 10, 5, 10));
+    config.set("general", "border_size", 2);
+    config.set("general", "allow_tearing", true);
+    config.set(
+        "general",
+        "col.inactive_border",
+        Color::new(1.0, 0.5, 0.0, 1.0),
+    );
+    config.set("misc", "vfr", false);
+    config.set("input.touchpad", "scroll_factor", 0.5);
+    config.set("cursor", "hotspot_padding", (1.0, 2.5));
+    config.set("", "exec-once", "waybar");
+    config.set("", "exec-once", String::from("hyprpaper"));
+
+    assert_eq!(
+        "$accent = rgb(33ccff)\n\ngeneral {\n    gaps_out = 5 10\n    col.inactive_border = rgb(ff8000)\n    allow_tearing = yes\n    border_size = 2\n}\n\nmisc {\n    vfr = 0\n}\n\ninput {\n    touchpad {\n        scroll_factor = 0.5\n    }\n}\n\ncursor {\n    hotspot_padding = 1 2.5\n}\n\nexec-once = hyprpaper\n",
+        config.to_string()
+    );
 }